@@ -0,0 +1,284 @@
+use crate::error::error::OxAgAudioToolError;
+use crate::playback::SoundHandle;
+use crate::sound_config::OxAgSoundConfig;
+use kira::clock::ClockHandle;
+use kira::manager::backend::Backend;
+use kira::manager::AudioManager;
+use kira::tween::{Tween, Value};
+use kira::{OutputDestination, StartTime, Volume};
+use robotics_lib::event::events::Event;
+use robotics_lib::world::environmental_conditions::WeatherType;
+use robotics_lib::world::tile::TileType;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// How many milliseconds in the future the stems get scheduled, so that they all start on the same sample
+const STEM_START_DELAY: u64 = 50;
+
+/// A single stem (e.g. pads, percussion or lead) of an [OxAgAdaptiveMusic]
+#[derive(Debug)]
+pub struct OxAgMusicStem {
+    pub(crate) sound_config: OxAgSoundConfig,
+    pub(crate) min_intensity: f64,
+    pub(crate) full_intensity: f64,
+}
+
+impl OxAgMusicStem {
+    /// # Params
+    /// - sound_config - the [OxAgSoundConfig] of the stem, it gets looped infinitely
+    /// - min_intensity - below this intensity the stem is silent
+    /// - full_intensity - above this intensity the stem plays at its configured volume,
+    ///   in between the volume fades in linearly
+    pub fn new(sound_config: OxAgSoundConfig, min_intensity: f64, full_intensity: f64) -> OxAgMusicStem {
+        let mut sound_config = sound_config;
+        sound_config.looped();
+
+        OxAgMusicStem {
+            sound_config,
+            min_intensity,
+            full_intensity,
+        }
+    }
+}
+
+/// Configures a music track made of synchronized stems whose volumes follow the intensity of the game
+///
+/// The intensity goes from 0 to 1 and it's the sum of:
+/// - the intensity of the [TileType] the robot is on
+/// - the intensity of the current [WeatherType]
+/// - the energy intensity, scaled by how depleted the robot energy is
+/// - the activity, which grows with every event that isn't a movement or a time change and decays over time
+///
+/// All the stems should have the same length, so that they stay aligned while looping.
+#[derive(Debug)]
+pub struct OxAgAdaptiveMusic {
+    pub(crate) stems: Vec<OxAgMusicStem>,
+    pub(crate) tile_intensity: HashMap<TileType, f64>,
+    pub(crate) weather_intensity: HashMap<WeatherType, f64>,
+    pub(crate) energy_intensity: f64,
+    pub(crate) activity_intensity: f64,
+    pub(crate) activity_half_life: Duration,
+    pub(crate) tween: Tween,
+}
+
+impl Default for OxAgAdaptiveMusic {
+    fn default() -> Self {
+        OxAgAdaptiveMusic {
+            stems: Vec::new(),
+            tile_intensity: HashMap::new(),
+            weather_intensity: HashMap::new(),
+            energy_intensity: 0.0,
+            activity_intensity: 0.0,
+            activity_half_life: Duration::from_secs(5),
+            tween: Tween {
+                duration: Duration::from_secs(2),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+impl OxAgAdaptiveMusic {
+    /// An adaptive music track without stems and with a constant intensity of 0
+    pub fn new() -> OxAgAdaptiveMusic {
+        OxAgAdaptiveMusic::default()
+    }
+
+    /// Adds a stem to the track
+    pub fn add_stem(&mut self, stem: OxAgMusicStem) {
+        self.stems.push(stem);
+    }
+
+    /// Sets the intensity added while the robot is on the given [TileType]
+    pub fn tile_intensity(&mut self, tile_type: TileType, intensity: f64) {
+        self.tile_intensity.insert(tile_type, intensity);
+    }
+
+    /// Sets the intensity added while the weather is the given [WeatherType]
+    pub fn weather_intensity(&mut self, weather_type: WeatherType, intensity: f64) {
+        self.weather_intensity.insert(weather_type, intensity);
+    }
+
+    /// Sets the intensity added when the robot energy is depleted, scaled down linearly as the energy grows
+    pub fn energy_intensity(&mut self, intensity: f64) {
+        self.energy_intensity = intensity;
+    }
+
+    /// # Params
+    /// - intensity - the intensity added by every event that isn't a movement or a time change
+    /// - half_life - the time it takes for the added intensity to halve
+    pub fn activity_intensity(&mut self, intensity: f64, half_life: Duration) {
+        self.activity_intensity = intensity;
+        self.activity_half_life = half_life;
+    }
+
+    /// The [Tween] used to fade the stems when the intensity changes
+    pub fn tween(&mut self, tween: Tween) {
+        self.tween = tween;
+    }
+}
+
+/// A playing stem
+struct Stem {
+    min_intensity: f64,
+    full_intensity: f64,
+    /// The configured amplitude of the stem
+    amplitude: f64,
+    handle: SoundHandle,
+}
+
+impl Stem {
+    /// Returns the volume of the stem for the given intensity
+    fn volume(&self, intensity: f64) -> Volume {
+        Volume::Amplitude(self.amplitude * stem_level(self.min_intensity, self.full_intensity, intensity))
+    }
+}
+
+/// Returns the level of a stem, from 0 to 1, for the given intensity
+fn stem_level(min_intensity: f64, full_intensity: f64, intensity: f64) -> f64 {
+    if full_intensity <= min_intensity {
+        return if intensity >= min_intensity { 1.0 } else { 0.0 };
+    }
+
+    ((intensity - min_intensity) / (full_intensity - min_intensity)).clamp(0.0, 1.0)
+}
+
+/// A playing [OxAgAdaptiveMusic]
+pub(crate) struct AdaptiveMusic {
+    stems: Vec<Stem>,
+    tile_intensity: HashMap<TileType, f64>,
+    weather_intensity: HashMap<WeatherType, f64>,
+    energy_intensity: f64,
+    activity_intensity: f64,
+    activity_half_life: Duration,
+    tween: Tween,
+    /// The [TileType] the robot is on
+    tile_type: Option<TileType>,
+    /// The current [WeatherType]
+    weather_type: Option<WeatherType>,
+    /// The intensity added by the recent events and when it was last updated, in tool time
+    activity: (f64, Duration),
+    /// The intensity the stems are currently set to
+    intensity: f64,
+}
+
+impl AdaptiveMusic {
+    /// Starts all the stems of the music on the same sample, scheduling them slightly in the future on the `timeline` clock
+    ///
+    /// # Params
+    /// - tile_type - the [TileType] the robot is on, if known already
+    /// - weather_type - the current [WeatherType], if known already
+    pub(crate) fn play<B: Backend>(
+        audio_manager: &mut AudioManager<B>,
+        timeline: &ClockHandle,
+        destination: OutputDestination,
+        music: &OxAgAdaptiveMusic,
+        tile_type: Option<TileType>,
+        weather_type: Option<WeatherType>,
+    ) -> Result<AdaptiveMusic, OxAgAudioToolError> {
+        let start_time = StartTime::ClockTime(timeline.time() + STEM_START_DELAY);
+        let mut stems = Vec::new();
+
+        for stem in music.stems.iter() {
+            let source = stem.sound_config.routed_to(destination).to_sound_source()?;
+            let amplitude = match source.settings().volume {
+                Value::Fixed(volume) => volume.as_amplitude(),
+                _ => 1.0,
+            };
+            let volume = Volume::Amplitude(amplitude * stem_level(stem.min_intensity, stem.full_intensity, 0.0));
+
+            stems.push(Stem {
+                min_intensity: stem.min_intensity,
+                full_intensity: stem.full_intensity,
+                amplitude,
+                handle: source.play(audio_manager, |settings| settings.start_time(start_time).volume(volume))?,
+            });
+        }
+
+        Ok(AdaptiveMusic {
+            stems,
+            tile_intensity: music.tile_intensity.clone(),
+            weather_intensity: music.weather_intensity.clone(),
+            energy_intensity: music.energy_intensity,
+            activity_intensity: music.activity_intensity,
+            activity_half_life: music.activity_half_life,
+            tween: music.tween,
+            tile_type,
+            weather_type,
+            activity: (0.0, Duration::ZERO),
+            intensity: 0.0,
+        })
+    }
+
+    /// Updates the state of the game the intensity depends on, `now` being the tool time
+    pub(crate) fn observe(&mut self, event: &Event, now: Duration) {
+        match event {
+            Event::Moved(tile, _) => self.tile_type = Some(tile.tile_type),
+            Event::TimeChanged(environmental_conditions) | Event::DayChanged(environmental_conditions) => {
+                self.weather_type = Some(environmental_conditions.get_weather_condition());
+            }
+            _ => self.activity = (self.activity(now) + self.activity_intensity, now),
+        }
+    }
+
+    /// Returns the intensity added by the recent events, decayed since the last one until the tool time `now`
+    fn activity(&self, now: Duration) -> f64 {
+        let (activity, since) = self.activity;
+
+        if self.activity_half_life.is_zero() {
+            return 0.0;
+        }
+
+        activity * 0.5_f64.powf(now.saturating_sub(since).as_secs_f64() / self.activity_half_life.as_secs_f64())
+    }
+
+    /// Fades the stems to the intensity of the current state of the game
+    ///
+    /// # Params
+    /// - energy_depletion - how depleted the robot energy is, from 0 (full) to 1 (empty)
+    /// - now - the tool time the activity is decayed until
+    pub(crate) fn update(&mut self, energy_depletion: f64, now: Duration) -> Result<(), OxAgAudioToolError> {
+        let tile_intensity = self.tile_type.and_then(|tile_type| self.tile_intensity.get(&tile_type)).copied();
+        let weather_intensity = self.weather_type.as_ref().and_then(|weather_type| self.weather_intensity.get(weather_type)).copied();
+
+        let intensity = (tile_intensity.unwrap_or(0.0)
+            + weather_intensity.unwrap_or(0.0)
+            + self.energy_intensity * energy_depletion
+            + self.activity(now))
+        .clamp(0.0, 1.0);
+
+        if (intensity - self.intensity).abs() < 0.01 {
+            return Ok(());
+        }
+
+        self.intensity = intensity;
+
+        for stem in self.stems.iter_mut() {
+            let volume = stem.volume(intensity);
+            stem.handle.set_volume(volume, self.tween)?;
+        }
+
+        Ok(())
+    }
+
+    /// Fades out and stops all the stems
+    pub(crate) fn stop(&mut self, tween: Tween) {
+        for stem in self.stems.iter_mut() {
+            let _ = stem.handle.stop(tween);
+        }
+    }
+
+    /// Fades out and pauses the stems
+    pub(crate) fn pause(&mut self, tween: Tween) {
+        for stem in self.stems.iter_mut() {
+            let _ = stem.handle.pause(tween);
+        }
+    }
+
+    /// Fades the stems paused by [AdaptiveMusic::pause] back in
+    pub(crate) fn resume(&mut self, tween: Tween) {
+        for stem in self.stems.iter_mut() {
+            let _ = stem.handle.unpause(tween);
+        }
+    }
+}
@@ -0,0 +1,253 @@
+use crate::error::error::OxAgAudioToolError;
+use crate::playback::{LoadedSound, SoundHandle};
+use crate::sound_config::OxAgSoundConfig;
+use kira::manager::backend::Backend;
+use kira::manager::AudioManager;
+use kira::sound::PlaybackState;
+use kira::tween::{Tween, Value};
+use kira::Volume;
+use rand::Rng;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::time::Duration;
+
+/// How often the one-shots of an [OxAgAmbientOneShots] get played
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OxAgOneShotFrequency {
+    /// Each call to [OxAgAudioTool::update](crate::OxAgAudioTool::update) plays a one-shot with the given probability, from 0 to 1
+    ChancePerUpdate(f64),
+    /// The one-shots are played at random intervals averaging the given duration, in tool time
+    /// (see [OxAgTimeSource](crate::timing::OxAgTimeSource))
+    MeanInterval(Duration),
+}
+
+/// Sporadic sounds played over a [TileType](robotics_lib::world::tile::TileType) or
+/// [WeatherType](robotics_lib::world::environmental_conditions::WeatherType) loop,
+/// e.g. birds on the grass or distant thunder during a monsoon
+///
+/// Every time a one-shot is due, one of the sounds gets picked at random and played
+/// with a random panning and volume within the configured ranges.
+#[derive(Debug, Clone)]
+pub struct OxAgAmbientOneShots {
+    pub(crate) sounds: Vec<OxAgSoundConfig>,
+    pub(crate) frequency: OxAgOneShotFrequency,
+    pub(crate) panning: (f64, f64),
+    pub(crate) volume: (f64, f64),
+}
+
+impl OxAgAmbientOneShots {
+    /// # Params
+    /// - frequency - how often the one-shots get played, see [OxAgOneShotFrequency]
+    pub fn new(frequency: OxAgOneShotFrequency) -> OxAgAmbientOneShots {
+        OxAgAmbientOneShots {
+            sounds: Vec::new(),
+            frequency,
+            panning: (0.5, 0.5),
+            volume: (1.0, 1.0),
+        }
+    }
+
+    /// Adds a sound to the ones picked at random
+    pub fn add_sound(&mut self, sound_config: OxAgSoundConfig) {
+        self.sounds.push(sound_config);
+    }
+
+    /// Sets the range the panning of each one-shot is picked from, 0 being hard left, 0.5 the center and 1 hard right
+    pub fn panning(&mut self, min: f64, max: f64) {
+        self.panning = (min, max);
+    }
+
+    /// Sets the range the volume of each one-shot is picked from, as an amplitude multiplying the configured volume of the sound
+    pub fn volume(&mut self, min: f64, max: f64) {
+        self.volume = (min, max);
+    }
+}
+
+/// The loaded sounds of an [OxAgAmbientOneShots]
+pub(crate) struct LoadedOneShots {
+    pub(crate) sounds: Vec<LoadedSound>,
+    frequency: OxAgOneShotFrequency,
+    panning: (f64, f64),
+    volume: (f64, f64),
+}
+
+impl LoadedOneShots {
+    pub(crate) fn new(one_shots: &OxAgAmbientOneShots, sounds: Vec<LoadedSound>) -> LoadedOneShots {
+        LoadedOneShots {
+            sounds,
+            frequency: one_shots.frequency,
+            panning: one_shots.panning,
+            volume: one_shots.volume,
+        }
+    }
+
+    /// Returns when the next one-shot is due, [None] if they are not played on a schedule
+    fn next_after(&self, rng: &mut impl Rng, now: Duration) -> Option<Duration> {
+        match self.frequency {
+            OxAgOneShotFrequency::ChancePerUpdate(_) => None,
+            OxAgOneShotFrequency::MeanInterval(mean) => {
+                // the intervals of events happening at a constant rate are exponentially distributed
+                let sample: f64 = rng.gen();
+                Some(now + mean.mul_f64(-(1.0 - sample).ln()))
+            }
+        }
+    }
+
+    /// Plays one of the sounds at random, with a random panning and volume
+    ///
+    /// Returns its handle together with the picked sound
+    fn play<B: Backend>(
+        &self,
+        audio_manager: &mut AudioManager<B>,
+        rng: &mut impl Rng,
+    ) -> Result<Option<(SoundHandle, &LoadedSound)>, OxAgAudioToolError> {
+        if self.sounds.is_empty() {
+            return Ok(None);
+        }
+
+        let sound = &self.sounds[rng.gen_range(0..self.sounds.len())];
+        let panning = random_in(rng, self.panning);
+        let amplitude = random_in(rng, self.volume);
+
+        let handle = sound.source.play(audio_manager, |settings| {
+            let volume = match settings.volume {
+                Value::Fixed(volume) => Value::Fixed(Volume::Amplitude(volume.as_amplitude() * amplitude)),
+                volume => volume,
+            };
+
+            settings.panning(panning).volume(volume)
+        })?;
+
+        Ok(Some((handle, sound)))
+    }
+}
+
+/// Returns a random value in the range, which can be empty
+fn random_in(rng: &mut impl Rng, (min, max): (f64, f64)) -> f64 {
+    match min < max {
+        true => rng.gen_range(min..max),
+        false => min,
+    }
+}
+
+/// Plays the one-shots of the active key (e.g. the [TileType](robotics_lib::world::tile::TileType) the robot is on)
+pub(crate) struct Ambience<K> {
+    pub(crate) one_shots: HashMap<K, LoadedOneShots>,
+    /// The key whose one-shots are played
+    active: Option<K>,
+    /// When the next one-shot of the active key is due, in tool time
+    next_at: Option<Duration>,
+    /// The one-shots of the active key that may still be playing
+    playing: Vec<SoundHandle>,
+}
+
+impl<K> Default for Ambience<K> {
+    fn default() -> Self {
+        Ambience {
+            one_shots: HashMap::new(),
+            active: None,
+            next_at: None,
+            playing: Vec::new(),
+        }
+    }
+}
+
+impl<K: Eq + Hash> Ambience<K> {
+    /// Sets the one-shots of the key, rescheduling them if it's active
+    pub(crate) fn set(&mut self, key: K, one_shots: LoadedOneShots, rng: &mut impl Rng, now: Duration) {
+        if self.active.as_ref() == Some(&key) {
+            self.next_at = one_shots.next_after(rng, now);
+        }
+
+        self.one_shots.insert(key, one_shots);
+    }
+
+    /// Makes the one-shots of `key` the ones being played, the ones of the previous key get cancelled
+    pub(crate) fn activate(&mut self, key: Option<K>, rng: &mut impl Rng, now: Duration) {
+        if self.active == key {
+            return;
+        }
+
+        for mut handle in self.playing.drain(..) {
+            let _ = handle.stop(Tween::default());
+        }
+
+        self.next_at = key.as_ref().and_then(|key| self.one_shots.get(key)).and_then(|one_shots| one_shots.next_after(rng, now));
+        self.active = key;
+    }
+
+    /// Returns the one-shots that are playing, together with the key they were played for
+    pub(crate) fn playing(&self) -> impl Iterator<Item = (&K, &SoundHandle)> {
+        self.playing
+            .iter()
+            .filter(|handle| handle.state() != PlaybackState::Stopped)
+            .filter_map(|handle| self.active.as_ref().map(|key| (key, handle)))
+    }
+
+    /// Returns the configurations of the one-shots of the key
+    pub(crate) fn configs(&self, key: &K) -> impl Iterator<Item = &OxAgSoundConfig> {
+        self.one_shots.get(key).into_iter().flat_map(|one_shots| one_shots.sounds.iter().map(|sound| &sound.config))
+    }
+
+    /// Fades out and stops the one-shots still playing, no key is active anymore
+    pub(crate) fn stop(&mut self, tween: Tween) {
+        for mut handle in self.playing.drain(..) {
+            let _ = handle.stop(tween);
+        }
+
+        self.active = None;
+        self.next_at = None;
+    }
+
+    /// Fades out and pauses the one-shots still playing
+    pub(crate) fn pause(&mut self, tween: Tween) {
+        for handle in self.playing.iter_mut() {
+            let _ = handle.pause(tween);
+        }
+    }
+
+    /// Fades the one-shots paused by [Ambience::pause] back in
+    pub(crate) fn resume(&mut self, tween: Tween) {
+        for handle in self.playing.iter_mut() {
+            let _ = handle.unpause(tween);
+        }
+    }
+
+    /// Plays a one-shot of the active key if it's due
+    ///
+    /// Returns how long the played one-shot lasts if it [ducks](OxAgSoundConfig::ducks) the other categories
+    pub(crate) fn update<B: Backend>(
+        &mut self,
+        audio_manager: &mut AudioManager<B>,
+        rng: &mut impl Rng,
+        now: Duration,
+    ) -> Result<Option<Duration>, OxAgAudioToolError> {
+        self.playing.retain(|handle| handle.state() != PlaybackState::Stopped);
+
+        let one_shots = match self.active.as_ref().and_then(|key| self.one_shots.get(key)) {
+            Some(one_shots) => one_shots,
+            None => return Ok(None),
+        };
+
+        let due = match (one_shots.frequency, self.next_at) {
+            (OxAgOneShotFrequency::ChancePerUpdate(chance), _) => rng.gen_bool(chance.clamp(0.0, 1.0)),
+            (OxAgOneShotFrequency::MeanInterval(_), Some(next_at)) => now >= next_at,
+            (OxAgOneShotFrequency::MeanInterval(_), None) => false,
+        };
+
+        if !due {
+            return Ok(None);
+        }
+
+        // a single one-shot is played even if several were due since the last update
+        self.next_at = one_shots.next_after(rng, now);
+
+        let (handle, sound) = match one_shots.play(audio_manager, rng)? {
+            Some(played) => played,
+            None => return Ok(None),
+        };
+        self.playing.push(handle);
+
+        Ok(sound.ducks.then(|| sound.source.duration()))
+    }
+}
@@ -0,0 +1,106 @@
+use kira::sound::static_sound::StaticSoundSettings;
+use kira::sound::PlaybackRate;
+use kira::tween::Value;
+use kira::Volume;
+use std::time::Duration;
+
+/// Maps the amount carried by an event (e.g. the energy recharged or the coins added to the backpack) to a value
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OxAgCurve {
+    /// Always returns the same value
+    Constant(f64),
+    /// Returns `base + slope * amount`, capped at `max`
+    Linear { base: f64, slope: f64, max: f64 },
+    /// Returns `base + scale * ln(1 + amount)`, capped at `max`<br>
+    /// It grows quickly for small amounts and then flattens out
+    Logarithmic { base: f64, scale: f64, max: f64 },
+}
+
+impl OxAgCurve {
+    /// Returns the value of the curve for the given amount
+    pub fn evaluate(&self, amount: usize) -> f64 {
+        let amount = amount as f64;
+
+        match *self {
+            OxAgCurve::Constant(value) => value,
+            OxAgCurve::Linear { base, slope, max } => (base + slope * amount).min(max),
+            OxAgCurve::Logarithmic { base, scale, max } => (base + scale * amount.ln_1p()).min(max),
+        }
+    }
+}
+
+/// Configures how a sound gets scaled by the amount carried by the event it's played for
+///
+/// Only [Event::EnergyRecharged](robotics_lib::event::events::Event::EnergyRecharged), [Event::EnergyConsumed](robotics_lib::event::events::Event::EnergyConsumed),
+/// [Event::AddedToBackpack](robotics_lib::event::events::Event::AddedToBackpack) and [Event::RemovedFromBackpack](robotics_lib::event::events::Event::RemovedFromBackpack) carry an amount.
+///
+/// # Example
+/// Three coin clinks for three coins, up to ten clinks, one every 80ms:
+/// ```rs
+/// let mut scaling = OxAgAmountScaling::new();
+/// scaling.repetitions(OxAgCurve::Linear { base: 0.0, slope: 1.0, max: 10.0 }, Duration::from_millis(80));
+///
+/// let mut coins = OxAgSoundConfig::new("assets/default/event/event_add_to_backpack.ogg");
+/// coins.amount_scaling(scaling);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct OxAgAmountScaling {
+    pub(crate) volume: Option<OxAgCurve>,
+    pub(crate) playback_rate: Option<OxAgCurve>,
+    pub(crate) repetitions: Option<OxAgCurve>,
+    pub(crate) repetition_interval: Duration,
+}
+
+impl OxAgAmountScaling {
+    /// The sound is played once, as configured
+    pub fn new() -> OxAgAmountScaling {
+        OxAgAmountScaling::default()
+    }
+
+    /// The volume of the sound gets multiplied by the amplitude returned by the curve
+    pub fn volume(&mut self, curve: OxAgCurve) {
+        self.volume = Some(curve);
+    }
+
+    /// The playback rate of the sound gets multiplied by the factor returned by the curve
+    pub fn playback_rate(&mut self, curve: OxAgCurve) {
+        self.playback_rate = Some(curve);
+    }
+
+    /// The sound gets repeated as many times as returned by the curve (rounded), waiting `interval` between each repetition
+    pub fn repetitions(&mut self, curve: OxAgCurve, interval: Duration) {
+        self.repetitions = Some(curve);
+        self.repetition_interval = interval;
+    }
+
+    /// Returns the settings scaled by the given amount
+    pub(crate) fn scale_settings(&self, settings: StaticSoundSettings, amount: usize) -> StaticSoundSettings {
+        let mut settings = settings;
+
+        if let Some(curve) = self.volume {
+            let amplitude = match settings.volume {
+                Value::Fixed(volume) => volume.as_amplitude(),
+                _ => 1.0,
+            };
+            settings.volume = Value::Fixed(Volume::Amplitude(amplitude * curve.evaluate(amount)));
+        }
+
+        if let Some(curve) = self.playback_rate {
+            let factor = match settings.playback_rate {
+                Value::Fixed(playback_rate) => playback_rate.as_factor(),
+                _ => 1.0,
+            };
+            settings.playback_rate = Value::Fixed(PlaybackRate::Factor(factor * curve.evaluate(amount)));
+        }
+
+        settings
+    }
+
+    /// Returns how many times the sound gets played for the given amount
+    pub(crate) fn repetitions_for(&self, amount: usize) -> usize {
+        match self.repetitions {
+            Some(curve) => curve.evaluate(amount).round().max(0.0) as usize,
+            None => 1,
+        }
+    }
+}
@@ -0,0 +1,267 @@
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use kira::manager::backend::cpal::{CpalBackend, Error};
+use kira::manager::backend::{Backend, Renderer};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The sample rate the audio is rendered at while there's no audio device
+const SILENT_SAMPLE_RATE: u32 = 48000;
+
+/// How often the audio gets rendered while there's no audio device
+const SILENT_RENDER_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Why the audio is not being played, shared between the backend and the tool
+pub(crate) type SilenceReason = Arc<Mutex<Option<String>>>;
+
+/// Configures what the [OxAgFallbackBackend] does when there's no audio device,
+/// see [OxAgAudioToolBuilder::silent_fallback](crate::builder::OxAgAudioToolBuilder::silent_fallback)
+#[derive(Debug, Clone, Default)]
+pub struct OxAgFallbackSettings {
+    pub(crate) silent_fallback: bool,
+    pub(crate) retry_interval: Option<Duration>,
+    pub(crate) silence_reason: SilenceReason,
+}
+
+enum State {
+    /// The audio is played by the audio device
+    Device(CpalBackend),
+    /// There's no audio device, the audio is rendered silently until it can be opened
+    Silent { retry_interval: Option<Duration> },
+}
+
+/// The default [Backend] of the tool: plays the audio through the default audio device like the [CpalBackend]
+/// or, if it can't be opened and the [silent fallback](crate::builder::OxAgAudioToolBuilder::silent_fallback) is enabled,
+/// keeps all the sounds, clocks and tracks running without playing them
+pub struct OxAgFallbackBackend {
+    state: State,
+    silence_reason: SilenceReason,
+    /// Tells the silent rendering thread to stop
+    stopped: Arc<AtomicBool>,
+}
+
+impl Backend for OxAgFallbackBackend {
+    type Settings = OxAgFallbackSettings;
+    type Error = Error;
+
+    fn setup(settings: Self::Settings) -> Result<(Self, u32), Self::Error> {
+        let (state, sample_rate) = match CpalBackend::setup(()) {
+            Ok((backend, sample_rate)) => (State::Device(backend), sample_rate),
+            Err(e) if settings.silent_fallback => {
+                set_silence_reason(&settings.silence_reason, Some(e.to_string()));
+                (State::Silent { retry_interval: settings.retry_interval }, SILENT_SAMPLE_RATE)
+            }
+            Err(e) => return Err(e),
+        };
+
+        Ok((
+            OxAgFallbackBackend {
+                state,
+                silence_reason: settings.silence_reason,
+                stopped: Arc::new(AtomicBool::new(false)),
+            },
+            sample_rate,
+        ))
+    }
+
+    fn start(&mut self, renderer: Renderer) -> Result<(), Self::Error> {
+        match &mut self.state {
+            State::Device(backend) => backend.start(renderer),
+            State::Silent { retry_interval } => {
+                let retry_interval = *retry_interval;
+                let silence_reason = self.silence_reason.clone();
+                let stopped = self.stopped.clone();
+
+                thread::spawn(move || render_silently::<CpalDevice>(renderer, retry_interval, silence_reason, stopped));
+
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Drop for OxAgFallbackBackend {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Records why the audio is not being played, a thread that panicked while recording it doesn't stop the others from doing so
+fn set_silence_reason(silence_reason: &SilenceReason, reason: Option<String>) {
+    *silence_reason.lock().unwrap_or_else(PoisonError::into_inner) = reason;
+}
+
+/// An audio device the silent rendering hands the renderer over to once it can be opened
+pub(crate) trait Device: Sized {
+    /// Opens the device, returning it together with its sample rate
+    fn open() -> Result<(Self, u32), String>;
+
+    /// Starts playing silence, the renderer is only handed over with [Device::play] once the device works
+    fn start(&mut self) -> Result<(), String>;
+
+    /// Plays the audio of the renderer on the started device
+    fn play(&mut self, renderer: Renderer);
+
+    /// Gives the renderer back if the device stopped playing, e.g. because it got disconnected
+    fn failure(&mut self) -> Option<(Renderer, String)>;
+}
+
+/// The default output device, played through cpal
+struct CpalDevice {
+    device: cpal::Device,
+    config: cpal::StreamConfig,
+    stream: Option<cpal::Stream>,
+    /// The renderer, shared with the stream once it's playing
+    renderer: Arc<Mutex<Option<Renderer>>>,
+    /// The error the stream stopped with, if any
+    error: Arc<Mutex<Option<String>>>,
+}
+
+impl CpalDevice {
+    /// Builds and starts a stream that plays silence until it's handed the renderer
+    fn start_stream(&self) -> Result<cpal::Stream, String> {
+        let channels = self.config.channels as usize;
+        let renderer = self.renderer.clone();
+        let error = self.error.clone();
+
+        let stream = self
+            .device
+            .build_output_stream(
+                &self.config,
+                move |data: &mut [f32], _: &cpal::OutputCallbackInfo| {
+                    match renderer.lock().unwrap_or_else(PoisonError::into_inner).as_mut() {
+                        Some(renderer) => render_into(renderer, data, channels),
+                        None => data.fill(0.0),
+                    }
+                },
+                move |e| *error.lock().unwrap_or_else(PoisonError::into_inner) = Some(e.to_string()),
+                None,
+            )
+            .map_err(|e| e.to_string())?;
+        stream.play().map_err(|e| e.to_string())?;
+
+        Ok(stream)
+    }
+}
+
+impl Device for CpalDevice {
+    fn open() -> Result<(Self, u32), String> {
+        let device = cpal::default_host().default_output_device().ok_or("no default output device")?;
+        let config = device.default_output_config().map_err(|e| e.to_string())?.config();
+        let sample_rate = config.sample_rate.0;
+
+        Ok((
+            CpalDevice {
+                device,
+                config,
+                stream: None,
+                renderer: Arc::default(),
+                error: Arc::default(),
+            },
+            sample_rate,
+        ))
+    }
+
+    fn start(&mut self) -> Result<(), String> {
+        self.stream = Some(self.start_stream()?);
+        Ok(())
+    }
+
+    fn play(&mut self, renderer: Renderer) {
+        *self.renderer.lock().unwrap_or_else(PoisonError::into_inner) = Some(renderer);
+    }
+
+    fn failure(&mut self) -> Option<(Renderer, String)> {
+        let error = self.error.lock().unwrap_or_else(PoisonError::into_inner).take()?;
+        self.stream = None;
+        let renderer = self.renderer.lock().unwrap_or_else(PoisonError::into_inner).take()?;
+
+        Some((renderer, error))
+    }
+}
+
+/// Renders the audio into the interleaved buffer of a device, the channels past the second one get silence
+fn render_into(renderer: &mut Renderer, data: &mut [f32], channels: usize) {
+    renderer.on_start_processing();
+
+    for frame in data.chunks_exact_mut(channels) {
+        let out = renderer.process();
+
+        if channels == 1 {
+            frame[0] = (out.left + out.right) / 2.0;
+        } else {
+            frame[0] = out.left;
+            frame[1] = out.right;
+            frame[2..].fill(0.0);
+        }
+    }
+}
+
+/// Renders the audio in real time without playing it, handing the renderer to the audio device as soon as it can be opened
+///
+/// The audio keeps on being rendered silently if the device can't start, or once it stops playing, until the next retry
+pub(crate) fn render_silently<D: Device>(
+    mut renderer: Renderer,
+    retry_interval: Option<Duration>,
+    silence_reason: SilenceReason,
+    stopped: Arc<AtomicBool>,
+) {
+    let mut rendered_until = Instant::now();
+    let mut retried_at = Instant::now();
+
+    while !stopped.load(Ordering::SeqCst) {
+        thread::sleep(SILENT_RENDER_INTERVAL);
+
+        let now = Instant::now();
+        let frames = ((now - rendered_until).as_secs_f64() * SILENT_SAMPLE_RATE as f64) as u64;
+        rendered_until += Duration::from_secs_f64(frames as f64 / SILENT_SAMPLE_RATE as f64);
+
+        renderer.on_start_processing();
+        for _ in 0..frames {
+            renderer.process();
+        }
+
+        let retry = retry_interval.is_some_and(|retry_interval| now - retried_at >= retry_interval);
+        if !retry {
+            continue;
+        }
+        retried_at = now;
+
+        // the renderer is only handed over once the device is open and started, so that it can't get lost
+        let (mut device, sample_rate) = match D::open() {
+            Ok(device) => device,
+            Err(e) => {
+                set_silence_reason(&silence_reason, Some(e));
+                continue;
+            }
+        };
+
+        if let Err(e) = device.start() {
+            set_silence_reason(&silence_reason, Some(e));
+            continue;
+        }
+
+        renderer.on_change_sample_rate(sample_rate);
+        device.play(renderer);
+        set_silence_reason(&silence_reason, None);
+
+        // the device keeps playing as long as it's alive and working
+        let reason = loop {
+            if stopped.load(Ordering::SeqCst) {
+                return;
+            }
+
+            if let Some((returned, reason)) = device.failure() {
+                renderer = returned;
+                break reason;
+            }
+
+            thread::sleep(SILENT_RENDER_INTERVAL);
+        };
+
+        set_silence_reason(&silence_reason, Some(reason));
+        renderer.on_change_sample_rate(SILENT_SAMPLE_RATE);
+        rendered_until = Instant::now();
+    }
+}
@@ -0,0 +1,278 @@
+use crate::backend::{OxAgFallbackBackend, SilenceReason};
+use crate::error::error::OxAgAudioToolError;
+use crate::limiter::MasterBus;
+use crate::sound_config::OxAgSoundConfig;
+use crate::timing::OxAgTimeSource;
+use crate::util::lock;
+use crate::validation::{OxAgMappingKey, OxAgValidationProblem, OxAgValidationReport};
+use crate::OxAgAudioTool;
+use kira::manager::backend::Backend;
+use kira::manager::{AudioManager, AudioManagerSettings};
+use kira::track::TrackBuilder;
+use kira::tween::Tween;
+use robotics_lib::event::events::Event;
+use robotics_lib::world::environmental_conditions::WeatherType;
+use robotics_lib::world::tile::TileType;
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Where the tool gets its [AudioManager] from
+enum AudioManagerSource<B: Backend> {
+    /// The tool starts its own audio manager with the settings, its categories play on the main track
+    New(AudioManagerSettings<B>),
+    /// The tool plays through an audio manager shared with the host application and other tools,
+    /// its categories play on a track of its own
+    Shared(Arc<Mutex<AudioManager<B>>>),
+}
+
+/// Builds an [OxAgAudioTool] step by step
+///
+/// # Example
+/// ```rs
+/// let audio = OxAgAudioToolBuilder::new()
+///     .event(Event::Ready, OxAgSoundConfig::new("assets/default/event/event_ready.ogg"))
+///     .tile_type(TileType::Grass, OxAgSoundConfig::new("assets/default/tile/tile_grass.ogg"))
+///     .weather_type(WeatherType::Rainy, OxAgSoundConfig::new("assets/default/weather/weather_rainy.ogg"))
+///     .loop_crossfade(Duration::from_millis(500))
+///     .master_volume(0.8)
+///     .background_music(OxAgSoundConfig::new_with_volume("assets/default/music.ogg", 0.5))
+///     .build()?;
+/// ```
+pub struct OxAgAudioToolBuilder<B: Backend = OxAgFallbackBackend> {
+    audio_manager: AudioManagerSource<B>,
+    events: Vec<(Event, OxAgSoundConfig)>,
+    tile_types: Vec<(TileType, OxAgSoundConfig)>,
+    weather_types: Vec<(WeatherType, OxAgSoundConfig)>,
+    loop_crossfade: Duration,
+    shared_asset_tween: Option<Tween>,
+    master_volume: f64,
+    background_music: Option<OxAgSoundConfig>,
+    silence_reason: SilenceReason,
+    time_source: OxAgTimeSource,
+    random_seed: Option<u64>,
+}
+
+impl OxAgAudioToolBuilder {
+    /// A builder without mappings, using the default backend with the default [AudioManagerSettings]
+    pub fn new() -> OxAgAudioToolBuilder {
+        OxAgAudioToolBuilder::with_settings(AudioManagerSettings::default())
+    }
+
+    /// When the audio device can't be opened (e.g. on a server or in a container), the tool still gets built
+    /// and keeps track of everything as usual, but no sound is heard, see [OxAgAudioTool::silence_reason]
+    ///
+    /// # Params
+    /// - retry_interval - if [Some], how often opening the audio device gets retried while the tool is silent,
+    ///   the sounds start being heard as soon as it succeeds
+    ///
+    /// # Side effects
+    /// Has no effect when the tool plays through a shared audio manager, see [OxAgAudioToolBuilder::with_audio_manager]
+    pub fn silent_fallback(mut self, retry_interval: Option<Duration>) -> Self {
+        if let AudioManagerSource::New(settings) = &mut self.audio_manager {
+            let backend_settings = &mut settings.backend_settings;
+            backend_settings.silent_fallback = true;
+            backend_settings.retry_interval = retry_interval;
+
+            self.silence_reason = backend_settings.silence_reason.clone();
+        }
+
+        self
+    }
+}
+
+impl Default for OxAgAudioToolBuilder {
+    fn default() -> Self {
+        OxAgAudioToolBuilder::new()
+    }
+}
+
+impl<B: Backend> OxAgAudioToolBuilder<B>
+where
+    OxAgAudioToolError: From<B::Error>,
+{
+    /// A builder without mappings, using the [Backend] `B` with the given [AudioManagerSettings]
+    ///
+    /// The main track of the settings gets the master limiter added at the end of its effects,
+    /// see [OxAgAudioTool::set_master_limiter]
+    pub fn with_settings(settings: AudioManagerSettings<B>) -> OxAgAudioToolBuilder<B> {
+        OxAgAudioToolBuilder::with_source(AudioManagerSource::New(settings))
+    }
+
+    /// A builder without mappings, playing through the given audio manager instead of starting its own
+    ///
+    /// The same audio manager can be shared by the host application and several tools (e.g. one per robot):
+    /// each tool gets its own track, with its own master volume and limiter, and its own category tracks,
+    /// so the tools don't interfere with each other
+    ///
+    /// # Capacity
+    /// Each tool uses a clock, plus one while it plays [sequenced music](OxAgAudioTool::play_sequenced_music),
+    /// and six mixer tracks. The default [AudioManagerSettings] only allow 8 clocks, so raise `clock_capacity`
+    /// (and `sub_track_capacity` for more than 20 tools) when creating the audio manager,
+    /// otherwise [build](OxAgAudioToolBuilder::build) fails with [CapacityExhausted](OxAgAudioToolError::CapacityExhausted)
+    pub fn with_audio_manager(audio_manager: Arc<Mutex<AudioManager<B>>>) -> OxAgAudioToolBuilder<B> {
+        OxAgAudioToolBuilder::with_source(AudioManagerSource::Shared(audio_manager))
+    }
+
+    fn with_source(audio_manager: AudioManagerSource<B>) -> OxAgAudioToolBuilder<B> {
+        OxAgAudioToolBuilder {
+            audio_manager,
+            events: Vec::new(),
+            tile_types: Vec::new(),
+            weather_types: Vec::new(),
+            loop_crossfade: Duration::ZERO,
+            shared_asset_tween: None,
+            master_volume: 1.0,
+            background_music: None,
+            silence_reason: SilenceReason::default(),
+            time_source: OxAgTimeSource::default(),
+            random_seed: None,
+        }
+    }
+
+    /// Plays the sound when the [Event] happens, the data carried by the [Event] is ignored
+    pub fn event(mut self, event: Event, sound_config: OxAgSoundConfig) -> Self {
+        self.events.push((event, sound_config));
+        self
+    }
+
+    /// Plays the sound while the robot is on the [TileType]
+    pub fn tile_type(mut self, tile_type: TileType, sound_config: OxAgSoundConfig) -> Self {
+        self.tile_types.push((tile_type, sound_config));
+        self
+    }
+
+    /// Plays the sound while the weather is the [WeatherType]
+    pub fn weather_type(mut self, weather_type: WeatherType, sound_config: OxAgSoundConfig) -> Self {
+        self.weather_types.push((weather_type, sound_config));
+        self
+    }
+
+    /// Sets how long a [TileType] or [WeatherType] loop takes to fade out while the one replacing it fades in,
+    /// see [OxAgAudioTool::set_loop_crossfade]
+    pub fn loop_crossfade(mut self, crossfade: Duration) -> Self {
+        self.loop_crossfade = crossfade;
+        self
+    }
+
+    /// See [OxAgAudioTool::set_shared_asset_tween]
+    pub fn shared_asset_tween(mut self, tween: Tween) -> Self {
+        self.shared_asset_tween = Some(tween);
+        self
+    }
+
+    /// Multiplies the volume of the whole mix by the provided amplitude
+    pub fn master_volume(mut self, amplitude: f64) -> Self {
+        self.master_volume = amplitude;
+        self
+    }
+
+    /// See [OxAgAudioTool::set_time_source]
+    pub fn time_source(mut self, source: OxAgTimeSource) -> Self {
+        self.time_source = source;
+        self
+    }
+
+    /// See [OxAgAudioTool::set_random_seed]
+    pub fn random_seed(mut self, seed: u64) -> Self {
+        self.random_seed = Some(seed);
+        self
+    }
+
+    /// Loops the sound in the background as soon as the tool is built, see [OxAgAudioTool::play_background_music]
+    pub fn background_music(mut self, sound_config: OxAgSoundConfig) -> Self {
+        self.background_music = Some(sound_config);
+        self
+    }
+
+    /// Validates the configuration, starts the audio manager and loads all the sounds
+    ///
+    /// # Errors
+    /// [OxAgAudioToolError::InvalidConfig] with every problem of the configuration, see [OxAgAudioToolBuilder::validate]
+    pub fn build(self) -> Result<OxAgAudioTool<B>, OxAgAudioToolError> {
+        let report = self.validate();
+        if !report.is_valid() {
+            return Err(OxAgAudioToolError::InvalidConfig(report));
+        }
+
+        let (audio_manager, owns_audio_manager, output, master_bus) = match self.audio_manager {
+            AudioManagerSource::New(mut settings) => {
+                let master_bus = MasterBus::add_to(&mut settings.main_track_builder);
+                let audio_manager = AudioManager::<B>::new(settings)?;
+                let output = audio_manager.main_track();
+
+                (Arc::new(Mutex::new(audio_manager)), true, output, master_bus)
+            }
+            AudioManagerSource::Shared(audio_manager) => {
+                let mut builder = TrackBuilder::new();
+                let master_bus = MasterBus::add_to(&mut builder);
+                let output = lock(&audio_manager)?.add_sub_track(builder)?;
+
+                (audio_manager, false, output, master_bus)
+            }
+        };
+
+        let mut tool = OxAgAudioTool::from_parts(
+            audio_manager,
+            output,
+            master_bus,
+            self.silence_reason,
+            &self.events,
+            &self.tile_types,
+            &self.weather_types,
+        )?;
+        tool.owns_audio_manager = owns_audio_manager;
+        tool.set_master_volume(self.master_volume, Tween::default())?;
+        tool.set_loop_crossfade(self.loop_crossfade);
+        tool.set_time_source(self.time_source);
+        tool.set_shared_asset_tween(self.shared_asset_tween);
+
+        if let Some(seed) = self.random_seed {
+            tool.set_random_seed(seed);
+        }
+
+        if let Some(background_music) = self.background_music.as_ref() {
+            tool.play_background_music(background_music)?;
+        }
+
+        Ok(tool)
+    }
+
+    /// Checks the whole configuration without loading any sound, returning every problem at once
+    /// together with the [Event]s, [TileType]s and [WeatherType]s that have no sound
+    ///
+    /// The audio files get opened to check that they can be decoded and that the loop regions fit in them
+    pub fn validate(&self) -> OxAgValidationReport {
+        let mut report = OxAgValidationReport::default();
+
+        if !self.master_volume.is_finite() || self.master_volume < 0.0 {
+            report.problems.push(OxAgValidationProblem::InvalidMasterVolume(self.master_volume));
+        }
+
+        let mappings = self
+            .events
+            .iter()
+            .map(|(event, sound_config)| (OxAgMappingKey::from(event), sound_config))
+            .chain(self.tile_types.iter().map(|(tile_type, sound_config)| (OxAgMappingKey::TileType(*tile_type), sound_config)))
+            .chain(
+                self.weather_types
+                    .iter()
+                    .map(|(weather_type, sound_config)| (OxAgMappingKey::WeatherType(weather_type.clone()), sound_config)),
+            )
+            .chain(self.background_music.iter().map(|sound_config| (OxAgMappingKey::BackgroundMusic, sound_config)));
+
+        let mut keys = HashSet::new();
+        for (key, sound_config) in mappings {
+            if keys.contains(&key) {
+                report.problems.push(OxAgValidationProblem::DuplicateMapping(key.clone()));
+            }
+
+            report.check_sound_config(key.clone(), sound_config);
+            keys.insert(key);
+        }
+
+        report.check_coverage(keys.iter());
+
+        report
+    }
+}
@@ -0,0 +1,102 @@
+use crate::playback::LoadedSound;
+use crate::sound_config::OxAgSoundConfig;
+use crate::util::content_key;
+use crate::validation::OxAgMappingKey;
+use robotics_lib::world::tile::Content;
+use std::collections::HashMap;
+
+/// Matches the [Content] on one side of a content transition
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OxAgContentMatch {
+    /// Matches any [Content], including a [Content] that was never seen before
+    Any,
+    /// Matches the given [Content], its properties are ignored
+    Content(Content),
+}
+
+impl OxAgContentMatch {
+    /// Converts the match to a content key, [None] meaning any content
+    fn key(&self) -> Option<String> {
+        match self {
+            OxAgContentMatch::Any => None,
+            OxAgContentMatch::Content(content) => Some(content_key(content).to_string()),
+        }
+    }
+}
+
+/// How far from the robot, in tiles along each axis, the last known [Content]s are remembered
+const KNOWN_CONTENT_RADIUS: usize = 32;
+
+/// Remembers the last known [Content] at each coordinate to detect content transitions
+#[derive(Default)]
+pub(crate) struct ContentTransitions {
+    /// Maps each (old content, new content) pair to its sound, [None] being a wildcard
+    sounds: HashMap<(Option<String>, Option<String>), LoadedSound>,
+    /// The last known [Content] at each coordinate, only around the robot so that it doesn't grow with the explored world
+    known_contents: HashMap<(usize, usize), Content>,
+}
+
+impl ContentTransitions {
+    /// Returns the [OxAgMappingKey] of the transition from `from` to `to`
+    pub(crate) fn key(from: &OxAgContentMatch, to: &OxAgContentMatch) -> OxAgMappingKey {
+        OxAgMappingKey::ContentTransition {
+            from: from.key(),
+            to: to.key(),
+        }
+    }
+
+    /// Maps the transition from `from` to `to` to the given sound
+    pub(crate) fn insert(&mut self, from: &OxAgContentMatch, to: &OxAgContentMatch, sound: LoadedSound) {
+        self.sounds.insert((from.key(), to.key()), sound);
+    }
+
+    /// Returns the configuration of the sound of the transition between the given content keys, [None] being a wildcard
+    pub(crate) fn config(&self, from: &Option<String>, to: &Option<String>) -> Option<&OxAgSoundConfig> {
+        self.sounds.get(&(from.clone(), to.clone())).map(|sound| &sound.config)
+    }
+
+    /// Returns the sounds of all the transitions
+    pub(crate) fn sounds_mut(&mut self) -> impl Iterator<Item = &mut LoadedSound> {
+        self.sounds.values_mut()
+    }
+
+    /// Remembers the [Content] the robot moved on without looking for a transition,
+    /// forgetting the ones farther than [KNOWN_CONTENT_RADIUS] from the robot
+    pub(crate) fn remember(&mut self, coordinates: (usize, usize), content: &Content) {
+        let (row, col) = coordinates;
+        self.known_contents
+            .retain(|(other_row, other_col), _| row.abs_diff(*other_row) <= KNOWN_CONTENT_RADIUS && col.abs_diff(*other_col) <= KNOWN_CONTENT_RADIUS);
+        self.known_contents.insert(coordinates, content.clone());
+    }
+
+    /// Remembers the [Content] at the given coordinates, returning the sound of the transition from the previous one
+    /// together with the [OxAgMappingKey] of the mapping it comes from
+    ///
+    /// The most specific mapping wins: exact matches on both sides come first, then the ones with a wildcard
+    /// on the new content, then the ones with a wildcard on the old content and finally the ones with two wildcards.
+    /// A coordinate seen for the first time only matches a wildcard on the old content.
+    pub(crate) fn transition(&mut self, coordinates: (usize, usize), content: &Content) -> Option<(OxAgMappingKey, &LoadedSound)> {
+        let previous = self.known_contents.insert(coordinates, content.clone());
+
+        if previous.as_ref() == Some(content) {
+            return None;
+        }
+
+        let from = previous.as_ref().map(|previous| content_key(previous).to_string());
+        let to = Some(content_key(content).to_string());
+
+        let candidates = [
+            (from.clone(), to.clone()),
+            (from, None),
+            (None, to),
+            (None, None),
+        ];
+
+        candidates
+            .into_iter()
+            .find_map(|(from, to)| {
+                let sound = self.sounds.get(&(from.clone(), to.clone()))?;
+                Some((OxAgMappingKey::ContentTransition { from, to }, sound))
+            })
+    }
+}
@@ -0,0 +1,128 @@
+use crate::error::error::OxAgAudioToolError;
+use crate::mixer::OxAgSoundCategory;
+use crate::sound_config::OxAgSoundConfig;
+use kira::tween::Tween;
+use robotics_lib::event::events::Event;
+use std::sync::mpsc::Sender;
+
+/// A command sent to the tool by an [OxAgAudioController]
+#[derive(Debug, Clone)]
+pub enum OxAgAudioCommand {
+    /// See [OxAgAudioTool::play_audio](crate::OxAgAudioTool::play_audio)
+    PlayAudio(OxAgSoundConfig),
+    /// See [OxAgAudioTool::play_audio_based_on_event](crate::OxAgAudioTool::play_audio_based_on_event)
+    Event(Event),
+    /// See [OxAgAudioTool::set_master_volume](crate::OxAgAudioTool::set_master_volume)
+    SetMasterVolume { amplitude: f64, tween: Tween },
+    /// See [OxAgAudioTool::set_muted](crate::OxAgAudioTool::set_muted)
+    SetMuted { muted: bool, tween: Tween },
+    /// See [OxAgAudioTool::pause](crate::OxAgAudioTool::pause)
+    Pause(Tween),
+    /// See [OxAgAudioTool::resume](crate::OxAgAudioTool::resume)
+    Resume(Tween),
+    /// See [OxAgAudioTool::stop_all](crate::OxAgAudioTool::stop_all)
+    StopAll(Tween),
+    /// See [OxAgAudioTool::stop_category](crate::OxAgAudioTool::stop_category)
+    StopCategory(OxAgSoundCategory, Tween),
+    /// See [OxAgAudioTool::play_background_music](crate::OxAgAudioTool::play_background_music)
+    PlayBackgroundMusic(OxAgSoundConfig),
+    /// See [OxAgAudioTool::stop_background_music](crate::OxAgAudioTool::stop_background_music)
+    StopBackgroundMusic(Tween),
+    /// See [OxAgAudioTool::stop_playlist](crate::OxAgAudioTool::stop_playlist)
+    StopPlaylist(Tween),
+    /// See [OxAgAudioTool::skip_track](crate::OxAgAudioTool::skip_track)
+    SkipTrack,
+}
+
+/// A handle to control the tool from any thread, e.g. from the UI while the tool lives inside the robot given to the runner
+///
+/// The commands are queued and executed in order the next time the tool processes an [Event]
+/// or [OxAgAudioTool::process_commands](crate::OxAgAudioTool::process_commands) gets called.
+/// The errors of the commands that fail are kept by the tool, see [OxAgAudioTool::pop_command_error](crate::OxAgAudioTool::pop_command_error).
+///
+/// # Example
+/// ```rs
+/// let controller = audio.controller();
+///
+/// thread::spawn(move || {
+///     controller.set_muted(true, Tween::default()).unwrap();
+/// });
+/// ```
+#[derive(Debug, Clone)]
+pub struct OxAgAudioController {
+    sender: Sender<OxAgAudioCommand>,
+}
+
+impl OxAgAudioController {
+    pub(crate) fn new(sender: Sender<OxAgAudioCommand>) -> OxAgAudioController {
+        OxAgAudioController { sender }
+    }
+
+    /// Queues the command
+    ///
+    /// # Errors
+    /// [OxAgAudioToolError::ToolDropped] if the tool doesn't exist anymore
+    pub fn send(&self, command: OxAgAudioCommand) -> Result<(), OxAgAudioToolError> {
+        self.sender.send(command).map_err(|_| OxAgAudioToolError::ToolDropped)
+    }
+
+    /// Queues an [OxAgAudioCommand::PlayAudio]
+    pub fn play_audio(&self, sound_config: OxAgSoundConfig) -> Result<(), OxAgAudioToolError> {
+        self.send(OxAgAudioCommand::PlayAudio(sound_config))
+    }
+
+    /// Queues an [OxAgAudioCommand::Event], the tool reacts to it as if it happened in the game
+    pub fn forward_event(&self, event: Event) -> Result<(), OxAgAudioToolError> {
+        self.send(OxAgAudioCommand::Event(event))
+    }
+
+    /// Queues an [OxAgAudioCommand::SetMasterVolume]
+    pub fn set_master_volume(&self, amplitude: f64, tween: Tween) -> Result<(), OxAgAudioToolError> {
+        self.send(OxAgAudioCommand::SetMasterVolume { amplitude, tween })
+    }
+
+    /// Queues an [OxAgAudioCommand::SetMuted]
+    pub fn set_muted(&self, muted: bool, tween: Tween) -> Result<(), OxAgAudioToolError> {
+        self.send(OxAgAudioCommand::SetMuted { muted, tween })
+    }
+
+    /// Queues an [OxAgAudioCommand::Pause]
+    pub fn pause(&self, tween: Tween) -> Result<(), OxAgAudioToolError> {
+        self.send(OxAgAudioCommand::Pause(tween))
+    }
+
+    /// Queues an [OxAgAudioCommand::Resume]
+    pub fn resume(&self, tween: Tween) -> Result<(), OxAgAudioToolError> {
+        self.send(OxAgAudioCommand::Resume(tween))
+    }
+
+    /// Queues an [OxAgAudioCommand::StopAll]
+    pub fn stop_all(&self, tween: Tween) -> Result<(), OxAgAudioToolError> {
+        self.send(OxAgAudioCommand::StopAll(tween))
+    }
+
+    /// Queues an [OxAgAudioCommand::StopCategory]
+    pub fn stop_category(&self, category: OxAgSoundCategory, tween: Tween) -> Result<(), OxAgAudioToolError> {
+        self.send(OxAgAudioCommand::StopCategory(category, tween))
+    }
+
+    /// Queues an [OxAgAudioCommand::PlayBackgroundMusic]
+    pub fn play_background_music(&self, sound_config: OxAgSoundConfig) -> Result<(), OxAgAudioToolError> {
+        self.send(OxAgAudioCommand::PlayBackgroundMusic(sound_config))
+    }
+
+    /// Queues an [OxAgAudioCommand::StopBackgroundMusic]
+    pub fn stop_background_music(&self, tween: Tween) -> Result<(), OxAgAudioToolError> {
+        self.send(OxAgAudioCommand::StopBackgroundMusic(tween))
+    }
+
+    /// Queues an [OxAgAudioCommand::StopPlaylist]
+    pub fn stop_playlist(&self, tween: Tween) -> Result<(), OxAgAudioToolError> {
+        self.send(OxAgAudioCommand::StopPlaylist(tween))
+    }
+
+    /// Queues an [OxAgAudioCommand::SkipTrack]
+    pub fn skip_track(&self) -> Result<(), OxAgAudioToolError> {
+        self.send(OxAgAudioCommand::SkipTrack)
+    }
+}
@@ -0,0 +1,284 @@
+use crate::error::error::OxAgAudioToolError;
+use crate::mixer::OxAgSoundCategory;
+use kira::track::effect::distortion::{DistortionBuilder, DistortionHandle};
+use kira::track::effect::filter::{FilterBuilder, FilterHandle};
+use kira::track::effect::reverb::{ReverbBuilder, ReverbHandle};
+use kira::track::effect::volume_control::{VolumeControlBuilder, VolumeControlHandle};
+use kira::track::TrackBuilder;
+use kira::tween::Tween;
+use kira::Volume;
+use robotics_lib::world::environmental_conditions::WeatherType;
+use robotics_lib::world::tile::TileType;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// The cutoff frequency, in hertz, at which a low-pass filter doesn't affect the sound anymore
+const OPEN_CUTOFF: f64 = 20000.0;
+
+/// An effect applied to the tracks of some [OxAgSoundCategory]s while the robot is in a given environment
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OxAgEffect {
+    /// Removes the frequencies above `cutoff` (in hertz), e.g. to sound underwater
+    LowPass { cutoff: f64 },
+    /// Makes the sound reverberate, e.g. in the mountains
+    /// # Properties
+    /// - feedback - how big the room sounds, from 0 to 1 (excluded)
+    /// - mix - how much of the reverberated sound is heard, from 0 (none) to 1 (only the reverberated sound)
+    Reverb { feedback: f64, mix: f64 },
+    /// Distorts the sound, e.g. near the lava
+    /// # Properties
+    /// - drive - how much the sound is amplified before being distorted, in decibels
+    /// - mix - how much of the distorted sound is heard, from 0 (none) to 1 (only the distorted sound)
+    Distortion { drive: f64, mix: f64 },
+    /// Makes the sound feel distant, e.g. in the fog, by removing the frequencies above `cutoff` (in hertz)
+    /// and multiplying the volume by `amplitude`
+    Muffle { cutoff: f64, amplitude: f64 },
+}
+
+/// Configures the effects applied while the robot is on a [TileType] or while the weather is a [WeatherType]
+///
+/// When both a [TileType] and a [WeatherType] chain are active, their effects are combined:
+/// the lowest cutoff wins, the strongest reverb and distortion win and the muffle amplitudes get multiplied.
+#[derive(Debug, Clone)]
+pub struct OxAgEffectChain {
+    pub(crate) effects: Vec<OxAgEffect>,
+    pub(crate) targets: Vec<OxAgSoundCategory>,
+    pub(crate) tween: Tween,
+}
+
+impl Default for OxAgEffectChain {
+    fn default() -> Self {
+        OxAgEffectChain {
+            effects: Vec::new(),
+            targets: Vec::new(),
+            tween: Tween {
+                duration: Duration::from_secs(1),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+impl OxAgEffectChain {
+    /// An empty chain applied to the tracks of all the [OxAgSoundCategory]s
+    pub fn new() -> OxAgEffectChain {
+        OxAgEffectChain::default()
+    }
+
+    /// Adds an effect to the chain
+    pub fn add_effect(&mut self, effect: OxAgEffect) {
+        self.effects.push(effect);
+    }
+
+    /// Applies the chain to the track of the given [OxAgSoundCategory]
+    ///
+    /// # Side effects
+    /// Once a target is set, the chain is only applied to its targets
+    pub fn target(&mut self, category: OxAgSoundCategory) {
+        self.targets.push(category);
+    }
+
+    /// The [Tween] used to fade the effects in and out when the environment changes
+    pub fn tween(&mut self, tween: Tween) {
+        self.tween = tween;
+    }
+
+    /// Returns whether the chain is applied to the track of the given category
+    fn targets(&self, category: OxAgSoundCategory) -> bool {
+        self.targets.is_empty() || self.targets.contains(&category)
+    }
+}
+
+/// The combined parameters of the active effects on a track
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct EffectParameters {
+    cutoff: Option<f64>,
+    reverb: Option<(f64, f64)>,
+    distortion: Option<(f64, f64)>,
+    amplitude: f64,
+}
+
+impl Default for EffectParameters {
+    fn default() -> Self {
+        EffectParameters {
+            cutoff: None,
+            reverb: None,
+            distortion: None,
+            amplitude: 1.0,
+        }
+    }
+}
+
+impl EffectParameters {
+    /// Combines the effect with the parameters
+    fn add(&mut self, effect: &OxAgEffect) {
+        match *effect {
+            OxAgEffect::LowPass { cutoff } => self.add_cutoff(cutoff),
+            OxAgEffect::Reverb { feedback, mix } => {
+                let (current_feedback, current_mix) = self.reverb.unwrap_or((0.0, 0.0));
+                self.reverb = Some((current_feedback.max(feedback), current_mix.max(mix)));
+            }
+            OxAgEffect::Distortion { drive, mix } => {
+                let (current_drive, current_mix) = self.distortion.unwrap_or((f64::NEG_INFINITY, 0.0));
+                self.distortion = Some((current_drive.max(drive), current_mix.max(mix)));
+            }
+            OxAgEffect::Muffle { cutoff, amplitude } => {
+                self.add_cutoff(cutoff);
+                self.amplitude *= amplitude;
+            }
+        }
+    }
+
+    fn add_cutoff(&mut self, cutoff: f64) {
+        self.cutoff = Some(self.cutoff.map_or(cutoff, |current| current.min(cutoff)));
+    }
+}
+
+/// The effects on the track of a category, neutral until an [OxAgEffectChain] is applied
+pub(crate) struct TrackEffects {
+    filter: FilterHandle,
+    reverb: ReverbHandle,
+    distortion: DistortionHandle,
+    volume: VolumeControlHandle,
+    parameters: EffectParameters,
+}
+
+impl TrackEffects {
+    /// Adds the neutral effects to the track
+    pub(crate) fn add_to(builder: &mut TrackBuilder) -> TrackEffects {
+        TrackEffects {
+            filter: builder.add_effect(FilterBuilder::new().cutoff(OPEN_CUTOFF).mix(0.0)),
+            reverb: builder.add_effect(ReverbBuilder::new().mix(0.0)),
+            distortion: builder.add_effect(DistortionBuilder::new().mix(0.0)),
+            volume: builder.add_effect(VolumeControlBuilder::new(Volume::Amplitude(1.0))),
+            parameters: EffectParameters::default(),
+        }
+    }
+
+    /// Tweens the effects to the given parameters
+    fn apply(&mut self, parameters: EffectParameters, tween: Tween) -> Result<(), OxAgAudioToolError> {
+        if parameters == self.parameters {
+            return Ok(());
+        }
+
+        match parameters.cutoff {
+            Some(cutoff) => {
+                self.filter.set_cutoff(cutoff, tween)?;
+                self.filter.set_mix(1.0, tween)?;
+            }
+            None => {
+                self.filter.set_cutoff(OPEN_CUTOFF, tween)?;
+                self.filter.set_mix(0.0, tween)?;
+            }
+        }
+
+        match parameters.reverb {
+            Some((feedback, mix)) => {
+                self.reverb.set_feedback(feedback, tween)?;
+                self.reverb.set_mix(mix, tween)?;
+            }
+            None => self.reverb.set_mix(0.0, tween)?,
+        }
+
+        match parameters.distortion {
+            Some((drive, mix)) => {
+                self.distortion.set_drive(Volume::Decibels(drive), tween)?;
+                self.distortion.set_mix(mix, tween)?;
+            }
+            None => self.distortion.set_mix(0.0, tween)?,
+        }
+
+        self.volume.set_volume(Volume::Amplitude(parameters.amplitude), tween)?;
+        self.parameters = parameters;
+
+        Ok(())
+    }
+}
+
+/// Applies the [OxAgEffectChain]s of the current [TileType] and [WeatherType]
+#[derive(Default)]
+pub(crate) struct EnvironmentEffects {
+    tile_chains: HashMap<TileType, OxAgEffectChain>,
+    weather_chains: HashMap<WeatherType, OxAgEffectChain>,
+    /// The [TileType] the robot is on
+    tile_type: Option<TileType>,
+    /// The current [WeatherType]
+    weather_type: Option<WeatherType>,
+}
+
+impl EnvironmentEffects {
+    /// Sets the chain of the [TileType], returns the [Tween] the effects should change with if it's active
+    pub(crate) fn set_tile_chain(&mut self, tile_type: TileType, chain: OxAgEffectChain) -> Option<Tween> {
+        let tween = chain.tween;
+        self.tile_chains.insert(tile_type, chain);
+
+        (self.tile_type == Some(tile_type)).then_some(tween)
+    }
+
+    /// Sets the chain of the [WeatherType], returns the [Tween] the effects should change with if it's active
+    pub(crate) fn set_weather_chain(&mut self, weather_type: WeatherType, chain: OxAgEffectChain) -> Option<Tween> {
+        let tween = chain.tween;
+        let active = self.weather_type.as_ref() == Some(&weather_type);
+        self.weather_chains.insert(weather_type, chain);
+
+        active.then_some(tween)
+    }
+
+    /// Returns the active chains
+    fn active_chains(&self) -> impl Iterator<Item = &OxAgEffectChain> {
+        let tile_chain = self.tile_type.and_then(|tile_type| self.tile_chains.get(&tile_type));
+        let weather_chain = self.weather_type.as_ref().and_then(|weather_type| self.weather_chains.get(weather_type));
+
+        tile_chain.into_iter().chain(weather_chain)
+    }
+
+    /// Makes the chain of the [TileType] the active one, the [TileType] being the debounced one the loop follows
+    ///
+    /// Returns the [Tween] the effects should change with if a chain was entered or left:
+    /// the chain being entered fades in with its own [Tween], otherwise the one being left fades out with its own
+    pub(crate) fn enter_tile(&mut self, tile_type: TileType) -> Option<Tween> {
+        if self.tile_type == Some(tile_type) {
+            return None;
+        }
+
+        let left = self.tile_type.and_then(|tile_type| self.tile_chains.get(&tile_type));
+        let tween = self.tile_chains.get(&tile_type).or(left).map(|chain| chain.tween);
+        self.tile_type = Some(tile_type);
+
+        tween
+    }
+
+    /// Makes the chain of the [WeatherType] the active one, see [EnvironmentEffects::enter_tile]
+    pub(crate) fn enter_weather(&mut self, weather_type: WeatherType) -> Option<Tween> {
+        if self.weather_type.as_ref() == Some(&weather_type) {
+            return None;
+        }
+
+        let left = self.weather_type.as_ref().and_then(|weather_type| self.weather_chains.get(weather_type));
+        let tween = self.weather_chains.get(&weather_type).or(left).map(|chain| chain.tween);
+        self.weather_type = Some(weather_type);
+
+        tween
+    }
+
+    /// Tweens the effects of the tracks to the combination of the active chains
+    pub(crate) fn apply<'a>(
+        &self,
+        tracks: impl Iterator<Item = (&'a OxAgSoundCategory, &'a mut TrackEffects)>,
+        tween: Tween,
+    ) -> Result<(), OxAgAudioToolError> {
+        for (category, effects) in tracks {
+            let mut parameters = EffectParameters::default();
+
+            for chain in self.active_chains().filter(|chain| chain.targets(*category)) {
+                for effect in chain.effects.iter() {
+                    parameters.add(effect);
+                }
+            }
+
+            effects.apply(parameters, tween)?;
+        }
+
+        Ok(())
+    }
+}
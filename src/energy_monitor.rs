@@ -0,0 +1,183 @@
+use crate::error::error::OxAgAudioToolError;
+use crate::playback::{LoadedSound, SoundHandle};
+use crate::sound_config::OxAgSoundConfig;
+use kira::manager::backend::Backend;
+use kira::manager::AudioManager;
+use kira::sound::PlaybackRate;
+use kira::tween::Tween;
+use robotics_lib::event::events::Event;
+use std::time::Duration;
+
+/// The energy level a new robot starts with
+pub const DEFAULT_ENERGY_LEVEL: usize = 1000;
+
+/// Configures the warning loop (e.g. a heartbeat or an alarm) played while the robot energy is low
+#[derive(Debug)]
+pub struct OxAgLowEnergyWarning {
+    pub(crate) sound_config: OxAgSoundConfig,
+    pub(crate) threshold: usize,
+    pub(crate) max_playback_rate: f64,
+    pub(crate) tween: Tween,
+}
+
+impl OxAgLowEnergyWarning {
+    /// # Params
+    /// - sound_config - the [OxAgSoundConfig] of the warning, it gets looped infinitely
+    /// - threshold - the warning plays while the energy level is below the threshold
+    pub fn new(sound_config: OxAgSoundConfig, threshold: usize) -> OxAgLowEnergyWarning {
+        let mut sound_config = sound_config;
+        sound_config.looped();
+
+        OxAgLowEnergyWarning {
+            sound_config,
+            threshold,
+            max_playback_rate: 2.0,
+            tween: Tween {
+                duration: Duration::from_millis(500),
+                ..Default::default()
+            },
+        }
+    }
+
+    /// The warning plays faster as the energy falls, from its normal speed at the threshold
+    /// up to `max_playback_rate` times its normal speed when the energy is depleted
+    pub fn max_playback_rate(&mut self, max_playback_rate: f64) {
+        self.max_playback_rate = max_playback_rate;
+    }
+
+    /// The [Tween] used to change the speed of the warning
+    pub fn tween(&mut self, tween: Tween) {
+        self.tween = tween;
+    }
+}
+
+/// The loaded low energy warning
+struct LowEnergyWarning {
+    sound: LoadedSound,
+    threshold: usize,
+    max_playback_rate: f64,
+    tween: Tween,
+    handle: Option<SoundHandle>,
+}
+
+/// Tracks the approximate energy level of the robot by accumulating the energy [Event]s
+pub(crate) struct EnergyMonitor {
+    /// The tracked energy level
+    pub(crate) level: usize,
+    /// The maximum energy level, recharges never exceed it
+    pub(crate) max_level: usize,
+    /// The warning played while the energy is low
+    warning: Option<LowEnergyWarning>,
+}
+
+impl Default for EnergyMonitor {
+    fn default() -> Self {
+        EnergyMonitor {
+            level: DEFAULT_ENERGY_LEVEL,
+            max_level: DEFAULT_ENERGY_LEVEL,
+            warning: None,
+        }
+    }
+}
+
+impl EnergyMonitor {
+    /// Replaces the warning, playing the given sound loaded from its [OxAgSoundConfig]
+    pub(crate) fn set_warning(&mut self, warning: &OxAgLowEnergyWarning, sound: LoadedSound) {
+        if let Some(handle) = self.warning.as_mut().and_then(|warning| warning.handle.as_mut()) {
+            let _ = handle.stop(Tween::default());
+        }
+
+        self.warning = Some(LowEnergyWarning {
+            sound,
+            threshold: warning.threshold,
+            max_playback_rate: warning.max_playback_rate,
+            tween: warning.tween,
+            handle: None,
+        });
+    }
+
+    /// Returns the sound of the warning, if any
+    pub(crate) fn warning_sound(&self) -> Option<&LoadedSound> {
+        self.warning.as_ref().map(|warning| &warning.sound)
+    }
+
+    /// Returns the sound of the warning, if any
+    pub(crate) fn warning_sound_mut(&mut self) -> Option<&mut LoadedSound> {
+        self.warning.as_mut().map(|warning| &mut warning.sound)
+    }
+
+    /// Returns the handle of the warning while it plays
+    pub(crate) fn warning_handle(&self) -> Option<&SoundHandle> {
+        self.warning.as_ref().and_then(|warning| warning.handle.as_ref())
+    }
+
+    /// Updates the energy level if the event is an energy event, returns whether it changed
+    pub(crate) fn track(&mut self, event: &Event) -> bool {
+        match event {
+            Event::EnergyRecharged(amount) => self.level = self.level.saturating_add(*amount).min(self.max_level),
+            Event::EnergyConsumed(amount) => self.level = self.level.saturating_sub(*amount),
+            _ => return false,
+        }
+
+        true
+    }
+
+    /// Returns how depleted the energy is, from 0 (full) to 1 (empty)
+    pub(crate) fn depletion(&self) -> f64 {
+        if self.max_level == 0 {
+            return 0.0;
+        }
+
+        1.0 - (self.level.min(self.max_level) as f64 / self.max_level as f64)
+    }
+
+    /// Fades out and stops the warning, it starts again with the next energy [Event] while the energy is low
+    pub(crate) fn stop_warning(&mut self, tween: Tween) {
+        if let Some(mut handle) = self.warning.as_mut().and_then(|warning| warning.handle.take()) {
+            let _ = handle.stop(tween);
+        }
+    }
+
+    /// Fades out and pauses the warning
+    pub(crate) fn pause_warning(&mut self, tween: Tween) {
+        if let Some(handle) = self.warning.as_mut().and_then(|warning| warning.handle.as_mut()) {
+            let _ = handle.pause(tween);
+        }
+    }
+
+    /// Fades the warning paused by [EnergyMonitor::pause_warning] back in
+    pub(crate) fn resume_warning(&mut self, tween: Tween) {
+        if let Some(handle) = self.warning.as_mut().and_then(|warning| warning.handle.as_mut()) {
+            let _ = handle.unpause(tween);
+        }
+    }
+
+    /// Starts, stops or speeds up the warning according to the energy level
+    pub(crate) fn update_warning<B: Backend>(&mut self, audio_manager: &mut AudioManager<B>) -> Result<(), OxAgAudioToolError> {
+        let warning = match self.warning.as_mut() {
+            Some(warning) => warning,
+            None => return Ok(()),
+        };
+
+        if self.level >= warning.threshold {
+            if let Some(mut handle) = warning.handle.take() {
+                let _ = handle.stop(warning.tween);
+            }
+
+            return Ok(());
+        }
+
+        let depletion = 1.0 - self.level as f64 / warning.threshold as f64;
+        let playback_rate = PlaybackRate::Factor(1.0 + (warning.max_playback_rate - 1.0) * depletion);
+
+        match warning.handle.as_mut() {
+            Some(handle) => handle.set_playback_rate(playback_rate, warning.tween)?,
+            None => {
+                let handle = warning.sound.source.play(audio_manager, |settings| settings.playback_rate(playback_rate))?;
+                warning.handle = Some(handle);
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -1,47 +1,180 @@
 pub mod error {
+    use crate::validation::{OxAgMappingKey, OxAgValidationReport};
     use kira::manager::backend::cpal::Error;
-    use kira::manager::error::PlaySoundError;
+    use kira::manager::error::{AddClockError, AddSubTrackError, PlaySoundError};
+    use kira::CommandError;
     use kira::sound::FromFileError;
     use std::fmt::{Display, Formatter};
 
+    /// A resource of the audio engine that can run out
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum OxAgResource {
+        /// The sounds playing at the same time
+        Sounds,
+        /// The clocks, each music and the tool itself use one
+        Clocks,
+        /// The mixer tracks, the tool uses one for each [OxAgSoundCategory](crate::mixer::OxAgSoundCategory)
+        SubTracks,
+        /// The commands sent to the audio thread that it didn't process yet
+        CommandQueue,
+    }
+
+    impl Display for OxAgResource {
+        fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+            match self {
+                OxAgResource::Sounds => write!(f, "sounds"),
+                OxAgResource::Clocks => write!(f, "clocks"),
+                OxAgResource::SubTracks => write!(f, "mixer tracks"),
+                OxAgResource::CommandQueue => write!(f, "command queue"),
+            }
+        }
+    }
+
     /// The one and only error returned by this library
+    ///
+    /// The variants wrapping an error of the kira library display it as is,
+    /// the others describe what went wrong and expose the cause via [std::error::Error::source]
     #[derive(Debug)]
     pub enum OxAgAudioToolError {
-        /// Audio manager issue, see the wrapped [Error]
-        AudioManagerError(Error),
-        /// File issue, probably a path to an audio file wasn't correct, see the wrapped [FromFileError]
-        FileError(FromFileError),
+        /// The audio backend couldn't be started, e.g. because there's no audio device, see the wrapped [Error]
+        BackendUnavailable(Error),
+        /// The audio file at `path` couldn't be loaded, see the wrapped [FromFileError]
+        FileError { path: String, source: FromFileError },
+        /// The audio engine ran out of the [OxAgResource]
+        CapacityExhausted(OxAgResource),
         /// Audio playing issue, see the wrapper [PlaySoundError]
         PlaySoundError(PlaySoundError<()>),
+        /// Streamed audio playing issue, see the wrapper [PlaySoundError]
+        PlayStreamingSoundError(PlaySoundError<FromFileError>),
+        /// Clock creation issue, see the wrapped [AddClockError]
+        AddClockError(AddClockError),
+        /// Mixer track creation issue, see the wrapped [AddSubTrackError]
+        AddSubTrackError(AddSubTrackError),
+        /// Issue sending a command to the audio thread, see the wrapped [CommandError]
+        CommandError(CommandError),
+        /// The configuration given to [OxAgAudioToolBuilder::build](crate::builder::OxAgAudioToolBuilder::build) is not valid,
+        /// the wrapped [OxAgValidationReport] lists all the problems
+        InvalidConfig(OxAgValidationReport),
+        /// The tool an [OxAgAudioController](crate::controller::OxAgAudioController) sends commands to doesn't exist anymore
+        ToolDropped,
+        /// The `operation` failed, for the sound mapped to `key` if any, `source` being the cause
+        Context {
+            operation: &'static str,
+            key: Option<OxAgMappingKey>,
+            source: Box<OxAgAudioToolError>,
+        },
+    }
+
+    impl OxAgAudioToolError {
+        /// Wraps the error with the operation that failed and the key of the sound it was for
+        pub(crate) fn context(self, operation: &'static str, key: Option<OxAgMappingKey>) -> OxAgAudioToolError {
+            OxAgAudioToolError::Context {
+                operation,
+                key,
+                source: Box::new(self),
+            }
+        }
+
+        /// Returns the innermost error, skipping the [OxAgAudioToolError::Context]s
+        pub fn root(&self) -> &OxAgAudioToolError {
+            match self {
+                OxAgAudioToolError::Context { source, .. } => source.root(),
+                _ => self,
+            }
+        }
     }
 
     impl Display for OxAgAudioToolError {
         fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
             let error_string = match self {
-                OxAgAudioToolError::AudioManagerError(e) => format!("{}", e),
-                OxAgAudioToolError::FileError(e) => format!("{}", e),
+                OxAgAudioToolError::BackendUnavailable(e) => format!("{}", e),
+                OxAgAudioToolError::FileError { path, .. } => format!("could not load the audio file {}", path),
+                OxAgAudioToolError::CapacityExhausted(resource) => format!("the audio engine ran out of {}", resource),
                 OxAgAudioToolError::PlaySoundError(e) => format!("{}", e),
+                OxAgAudioToolError::PlayStreamingSoundError(e) => format!("{}", e),
+                OxAgAudioToolError::AddClockError(e) => format!("{}", e),
+                OxAgAudioToolError::AddSubTrackError(e) => format!("{}", e),
+                OxAgAudioToolError::CommandError(e) => format!("{}", e),
+                OxAgAudioToolError::InvalidConfig(e) => format!("invalid configuration: {}", e),
+                OxAgAudioToolError::ToolDropped => "the audio tool has been dropped".to_string(),
+                OxAgAudioToolError::Context { operation, key: Some(key), .. } => format!("{} for the {} failed", operation, key),
+                OxAgAudioToolError::Context { operation, key: None, .. } => format!("{} failed", operation),
             };
 
             write!(f, "{}", error_string)
         }
     }
 
-    impl From<Error> for OxAgAudioToolError {
-        fn from(value: Error) -> Self {
-            OxAgAudioToolError::AudioManagerError(value)
+    impl std::error::Error for OxAgAudioToolError {
+        fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+            match self {
+                OxAgAudioToolError::BackendUnavailable(e) => e.source(),
+                OxAgAudioToolError::FileError { source, .. } => Some(source),
+                OxAgAudioToolError::CapacityExhausted(_) => None,
+                OxAgAudioToolError::PlaySoundError(e) => e.source(),
+                OxAgAudioToolError::PlayStreamingSoundError(e) => e.source(),
+                OxAgAudioToolError::AddClockError(e) => e.source(),
+                OxAgAudioToolError::AddSubTrackError(e) => e.source(),
+                OxAgAudioToolError::CommandError(e) => e.source(),
+                OxAgAudioToolError::InvalidConfig(_) => None,
+                OxAgAudioToolError::ToolDropped => None,
+                OxAgAudioToolError::Context { source, .. } => Some(source.as_ref()),
+            }
         }
     }
 
-    impl From<FromFileError> for OxAgAudioToolError {
-        fn from(value: FromFileError) -> Self {
-            OxAgAudioToolError::FileError(value)
+    impl From<Error> for OxAgAudioToolError {
+        fn from(value: Error) -> Self {
+            OxAgAudioToolError::BackendUnavailable(value)
         }
     }
 
     impl From<PlaySoundError<()>> for OxAgAudioToolError {
         fn from(value: PlaySoundError<()>) -> Self {
-            OxAgAudioToolError::PlaySoundError(value)
+            match value {
+                PlaySoundError::SoundLimitReached => OxAgAudioToolError::CapacityExhausted(OxAgResource::Sounds),
+                PlaySoundError::CommandError(e) => e.into(),
+                _ => OxAgAudioToolError::PlaySoundError(value),
+            }
+        }
+    }
+
+    impl From<PlaySoundError<FromFileError>> for OxAgAudioToolError {
+        fn from(value: PlaySoundError<FromFileError>) -> Self {
+            match value {
+                PlaySoundError::SoundLimitReached => OxAgAudioToolError::CapacityExhausted(OxAgResource::Sounds),
+                PlaySoundError::CommandError(e) => e.into(),
+                _ => OxAgAudioToolError::PlayStreamingSoundError(value),
+            }
+        }
+    }
+
+    impl From<AddClockError> for OxAgAudioToolError {
+        fn from(value: AddClockError) -> Self {
+            match value {
+                AddClockError::ClockLimitReached => OxAgAudioToolError::CapacityExhausted(OxAgResource::Clocks),
+                AddClockError::CommandError(e) => e.into(),
+                _ => OxAgAudioToolError::AddClockError(value),
+            }
+        }
+    }
+
+    impl From<AddSubTrackError> for OxAgAudioToolError {
+        fn from(value: AddSubTrackError) -> Self {
+            match value {
+                AddSubTrackError::SubTrackLimitReached => OxAgAudioToolError::CapacityExhausted(OxAgResource::SubTracks),
+                AddSubTrackError::CommandError(e) => e.into(),
+                _ => OxAgAudioToolError::AddSubTrackError(value),
+            }
+        }
+    }
+
+    impl From<CommandError> for OxAgAudioToolError {
+        fn from(value: CommandError) -> Self {
+            match value {
+                CommandError::CommandQueueFull => OxAgAudioToolError::CapacityExhausted(OxAgResource::CommandQueue),
+                _ => OxAgAudioToolError::CommandError(value),
+            }
         }
     }
 }
@@ -1,5 +1,23 @@
+pub mod builder;
+pub mod backend;
+pub mod controller;
 pub mod sound_config;
+pub mod amount_scaling;
+pub mod energy_monitor;
+pub mod adaptive_music;
+pub mod music_sequencer;
+pub mod playlist;
+pub mod mixer;
+pub mod effects;
+pub mod limiter;
+pub mod loudness;
+pub mod ambience;
+pub mod timing;
+pub mod validation;
+pub mod status;
 pub mod error;
+pub mod content_transition;
+mod playback;
 mod util;
 #[cfg(test)]
 mod test;
@@ -11,7 +29,26 @@ mod test;
 ///   This library will handle the switching between tile types and play the appropriate audio
 /// - assign each [WeatherType] its own sound<br>
 ///   This library will handle the switching between weather types and play the appropriate audio
+/// - assign each [Content] its own ambience loop<br>
+///   This library will play it while the robot is on (or next to) a tile with that content
 /// - react to [Event]s with sound
+/// - track the robot energy and warn when it's running low
+/// - play adaptive music, made of stems that follow the intensity of the game
+/// - play sequenced music, made of sections that follow the world and change on the beat
+/// - play a playlist of background music, streamed from the files
+/// - stream long sounds from their files instead of decoding them in memory
+/// - duck the music and the ambience while important sounds play
+/// - apply effects (e.g. an underwater filter or a mountain reverb) depending on the [TileType] and the [WeatherType]
+/// - limit the master bus to prevent clipping, and count the clipped frames
+/// - normalize the loudness of the sounds of each category
+/// - validate the whole configuration up front, reporting every problem and the unmapped keys at once
+/// - keep running silently when there's no audio device, e.g. on a server
+/// - share the audio manager with the host application and other tools, e.g. one per robot
+/// - control the tool from other threads, e.g. mute it from the UI
+/// - measure the timers in game ticks, for a behaviour that's the same on every run
+/// - play sporadic one-shots over the [TileType] and [WeatherType] loops, e.g. birds on the grass
+/// - pause, resume and stop everything, or a single category, and set the master volume in decibels
+/// - ask which loops and one-shots are playing and which sounds are mapped to a key
 /// - play any sound from a give file with minimal setup
 ///
 /// # Example
@@ -160,32 +197,120 @@ mod test;
 /// }
 /// ```
 
+use crate::backend::{OxAgFallbackBackend, SilenceReason};
+use crate::builder::OxAgAudioToolBuilder;
+use crate::controller::{OxAgAudioCommand, OxAgAudioController};
 use crate::sound_config::OxAgSoundConfig;
+use crate::adaptive_music::{AdaptiveMusic, OxAgAdaptiveMusic};
+use crate::ambience::{Ambience, LoadedOneShots, OxAgAmbientOneShots};
+use crate::music_sequencer::{OxAgSequencedMusic, SequencedMusic};
+use crate::playlist::{OxAgPlaylist, OxAgPlaylistEvent, Playlist};
+use crate::content_transition::{ContentTransitions, OxAgContentMatch};
+use crate::effects::OxAgEffectChain;
+use crate::limiter::{MasterBus, OxAgLimiter};
+use crate::loudness::{Loudness, OxAgLoudnessNormalization};
+use crate::energy_monitor::{EnergyMonitor, OxAgLowEnergyWarning};
+use crate::mixer::{Mixer, OxAgDucking, OxAgSoundCategory};
 use crate::error::error::OxAgAudioToolError;
-use crate::util::event_key;
-use kira::manager::backend::DefaultBackend;
-use kira::manager::{AudioManager, AudioManagerSettings};
-use kira::sound::static_sound::{StaticSoundData, StaticSoundHandle};
+use crate::playback::{track_one_shot, Debounce, LoadedSound, LoopSwitcher, PlayingLoop, PlayingOneShot, SoundHandle};
+use crate::status::OxAgSoundStatus;
+use crate::util::{content_key, event_amount, event_key, lock};
+use crate::validation::OxAgMappingKey;
+use crate::timing::{OxAgTimeSource, ToolClock};
+use kira::manager::backend::Backend;
+use kira::clock::{ClockHandle, ClockSpeed};
+use kira::manager::AudioManager;
+use kira::sound::PlaybackState;
+use kira::track::{TrackHandle, TrackId};
 use kira::tween::Tween;
+use kira::{StartTime, Volume};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use robotics_lib::energy::Energy;
 use robotics_lib::event::events::Event;
 use robotics_lib::world::environmental_conditions::WeatherType;
-use robotics_lib::world::tile::TileType;
-use std::collections::HashMap;
-
-/// Struct used to play the various sounds
-pub struct OxAgAudioTool {
-    /// Maps each [Event] to a [StaticSoundData]
-    event_to_sound_data: HashMap<String, StaticSoundData>,
-    /// Maps each [TileType] to a [StaticSoundData]
-    tile_type_to_sound_data: HashMap<TileType, StaticSoundData>,
-    /// Maps each [WeatherType] to a [StaticSoundData]
-    weather_type_to_sound_data: HashMap<WeatherType, StaticSoundData>,
-    /// The manager for the audio
-    audio_manager: AudioManager,
-    /// The currently playing sound [StaticSoundHandle] that depends on the given [TileType]
-    current_tile_sound: Option<(TileType, StaticSoundHandle)>,
-    /// The currently playing sound [StaticSoundHandle] that depends on the given [WeatherType]
-    current_weather_sound: Option<(WeatherType, StaticSoundHandle)>,
+use robotics_lib::world::tile::{Content, Tile, TileType};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Struct used to play the various sounds, see [OxAgAudioToolBuilder] to configure it
+pub struct OxAgAudioTool<B: Backend = OxAgFallbackBackend> {
+    /// Maps each [Event] to a [LoadedSound]
+    event_to_sound_data: HashMap<String, LoadedSound>,
+    /// Maps each [TileType] to a [LoadedSound]
+    tile_type_to_sound_data: HashMap<TileType, LoadedSound>,
+    /// Maps each [WeatherType] to a [LoadedSound]
+    weather_type_to_sound_data: HashMap<WeatherType, LoadedSound>,
+    /// Maps each kind of [Content] to a [LoadedSound]
+    content_to_sound_data: HashMap<String, LoadedSound>,
+    /// Maps the transitions between [Content]s to a [StaticSoundData]
+    content_transitions: ContentTransitions,
+    /// The manager for the audio, possibly shared with the host application and other tools
+    audio_manager: Arc<Mutex<AudioManager<B>>>,
+    /// Whether the tool created the audio manager, instead of sharing it, set by [OxAgAudioToolBuilder::build]
+    owns_audio_manager: bool,
+    /// The track all the categories play on: the main track, or a track of its own when the audio manager is shared
+    output: TrackHandle,
+    /// Why the audio is not being heard, if the backend fell back to silence
+    silence_reason: SilenceReason,
+    /// The mixer track of each [OxAgSoundCategory]
+    mixer: Mixer,
+    /// The limiter and the clip counter on the main track
+    master_bus: MasterBus,
+    /// Normalizes the loudness of the loaded sounds
+    loudness: Loudness,
+    /// A clock ticking every millisecond, used to schedule sounds in the future
+    timeline: ClockHandle,
+    /// The currently playing loop that depends on the given [TileType]
+    current_tile_sound: Option<PlayingLoop<TileType>>,
+    /// The currently playing loop that depends on the given [WeatherType]
+    current_weather_sound: Option<PlayingLoop<WeatherType>>,
+    /// The currently playing loops that depend on the [Content] around the robot
+    current_content_sounds: Vec<PlayingLoop<String>>,
+    /// Whether the [Content] loops follow the surroundings given to [OxAgAudioTool::play_audio_based_on_surroundings]
+    content_from_surroundings: bool,
+    /// Switches the [TileType] and [WeatherType] loops, remembering where each one was stopped
+    loop_switcher: LoopSwitcher,
+    /// Decides when the robot has been on a new [TileType] long enough to switch loop
+    tile_debounce: Debounce<TileType>,
+    /// The time the timers of the tool are measured in
+    clock: ToolClock,
+    /// The [TileType] the robot was last seen on
+    tile_type: Option<TileType>,
+    /// The [WeatherType] last seen
+    weather_type: Option<WeatherType>,
+    /// Plays the one-shots of the debounced [TileType] the robot is on
+    tile_ambience: Ambience<TileType>,
+    /// Plays the one-shots of the current [WeatherType]
+    weather_ambience: Ambience<WeatherType>,
+    /// Picks when and which ambient one-shots get played and where the random loops start, see [OxAgAudioTool::set_random_seed]
+    rng: StdRng,
+    /// Tracks the energy of the robot and plays the low energy warning
+    energy_monitor: EnergyMonitor,
+    /// The playing adaptive music, if any
+    adaptive_music: Option<AdaptiveMusic>,
+    /// The playing sequenced music, if any
+    sequenced_music: Option<SequencedMusic>,
+    /// The playing playlist, if any
+    playlist: Option<Playlist>,
+    /// The playing background music, if any
+    background_music: Option<(OxAgSoundConfig, SoundHandle)>,
+    /// The master volume, as an amplitude, while not muted
+    master_volume: f64,
+    /// Whether the tool is silenced, see [OxAgAudioTool::set_muted]
+    muted: bool,
+    /// Whether the audio is paused, see [OxAgAudioTool::pause]
+    paused: bool,
+    /// The [OxAgSoundCategory::Event] one-shots that may still be playing
+    event_sounds: Vec<PlayingOneShot>,
+    /// Cloned into every [OxAgAudioController]
+    command_sender: Sender<OxAgAudioCommand>,
+    /// The commands sent by the [OxAgAudioController]s, not processed yet
+    commands: Receiver<OxAgAudioCommand>,
+    /// The errors of the commands that failed, not popped yet
+    command_errors: VecDeque<OxAgAudioToolError>,
 }
 
 impl OxAgAudioTool {
@@ -193,38 +318,117 @@ impl OxAgAudioTool {
     /// - event_to_sound_config - a [HashMap] that maps 0 or more [Event]s to a given [OxAgSoundConfig]
     /// - tile_type_to_sound_config - a [HashMap] that maps 0 or more [TileType]s to a given [OxAgSoundConfig]
     /// - weather_type_to_sound_config - a [WeatherType] that maps 0 or more [TileType]s to a given [OxAgSoundConfig]
+    ///
+    /// Shorthand for an [OxAgAudioToolBuilder] with the given mappings, see it for all the other options
     pub fn new(
         event_to_sound_config: HashMap<Event, OxAgSoundConfig>,
         tile_type_to_sound_config: HashMap<TileType, OxAgSoundConfig>,
         weather_type_to_sound_config: HashMap<WeatherType, OxAgSoundConfig>,
     ) -> Result<OxAgAudioTool, OxAgAudioToolError> {
-        let audio_manager =
-            AudioManager::<DefaultBackend>::new(AudioManagerSettings::default())?;
+        let mut builder = OxAgAudioToolBuilder::new();
+
+        for (event, config) in event_to_sound_config {
+            builder = builder.event(event, config);
+        }
+
+        for (tile_type, config) in tile_type_to_sound_config {
+            builder = builder.tile_type(tile_type, config);
+        }
+
+        for (weather_type, config) in weather_type_to_sound_config {
+            builder = builder.weather_type(weather_type, config);
+        }
+
+        builder.build()
+    }
+}
+
+impl<B: Backend> OxAgAudioTool<B> {
+    /// Creates the tool on top of a started audio manager, loading the sounds of the mappings
+    pub(crate) fn from_parts(
+        audio_manager: Arc<Mutex<AudioManager<B>>>,
+        output: TrackHandle,
+        master_bus: MasterBus,
+        silence_reason: SilenceReason,
+        event_to_sound_config: &[(Event, OxAgSoundConfig)],
+        tile_type_to_sound_config: &[(TileType, OxAgSoundConfig)],
+        weather_type_to_sound_config: &[(WeatherType, OxAgSoundConfig)],
+    ) -> Result<OxAgAudioTool<B>, OxAgAudioToolError> {
+        let (command_sender, commands) = mpsc::channel();
+
+        let (timeline, mixer) = {
+            let mut audio_manager = lock(&audio_manager)?;
 
-        let mut event_to_sound_data: HashMap<String, StaticSoundData> = HashMap::new();
-        let mut tile_type_to_sound_data: HashMap<TileType, StaticSoundData> = HashMap::new();
-        let mut weather_type_to_sound_data: HashMap<WeatherType, StaticSoundData> =
+            let timeline = audio_manager.add_clock(ClockSpeed::TicksPerSecond(1000.0))?;
+            timeline.start()?;
+
+            (timeline, Mixer::new(&mut audio_manager, output.id())?)
+        };
+
+        let mut event_to_sound_data: HashMap<String, LoadedSound> = HashMap::new();
+        let mut tile_type_to_sound_data: HashMap<TileType, LoadedSound> = HashMap::new();
+        let mut weather_type_to_sound_data: HashMap<WeatherType, LoadedSound> =
             HashMap::new();
 
+        let load = |category: OxAgSoundCategory, key: OxAgMappingKey, config: &OxAgSoundConfig| {
+            mixer
+                .load(category, config)
+                .map_err(|e| e.context("loading the sound", Some(key)))
+        };
+
         for (event, config) in event_to_sound_config.iter() {
-            event_to_sound_data.insert(event_key(event).to_string(), config.to_sound_data()?);
+            let key = OxAgMappingKey::from(event);
+            event_to_sound_data.insert(event_key(event).to_string(), load(OxAgSoundCategory::Event, key, config)?);
         }
 
         for (tile_type, config) in tile_type_to_sound_config.iter() {
-            tile_type_to_sound_data.insert(tile_type.clone(), config.to_sound_data()?);
+            let key = OxAgMappingKey::TileType(*tile_type);
+            tile_type_to_sound_data.insert(*tile_type, load(OxAgSoundCategory::Tile, key, config)?);
         }
 
         for (weather_type, config) in weather_type_to_sound_config.iter() {
-            weather_type_to_sound_data.insert(weather_type.clone(), config.to_sound_data()?);
+            let key = OxAgMappingKey::WeatherType(weather_type.clone());
+            weather_type_to_sound_data.insert(weather_type.clone(), load(OxAgSoundCategory::Weather, key, config)?);
         }
 
         Ok(OxAgAudioTool {
             event_to_sound_data,
             tile_type_to_sound_data,
             weather_type_to_sound_data,
+            content_to_sound_data: HashMap::new(),
+            content_transitions: ContentTransitions::default(),
             audio_manager,
+            owns_audio_manager: false,
+            output,
+            silence_reason,
+            mixer,
+            master_bus,
+            loudness: Loudness::default(),
+            timeline,
             current_tile_sound: None,
             current_weather_sound: None,
+            current_content_sounds: Vec::new(),
+            content_from_surroundings: false,
+            loop_switcher: LoopSwitcher::default(),
+            tile_debounce: Debounce::default(),
+            clock: ToolClock::default(),
+            tile_type: None,
+            weather_type: None,
+            tile_ambience: Ambience::default(),
+            weather_ambience: Ambience::default(),
+            rng: StdRng::from_entropy(),
+            energy_monitor: EnergyMonitor::default(),
+            adaptive_music: None,
+            sequenced_music: None,
+            playlist: None,
+            background_music: None,
+            master_volume: 1.0,
+            muted: false,
+            paused: false,
+            event_sounds: Vec::new(),
+            command_sender,
+            commands,
+            command_errors: VecDeque::new(),
         })
     }
 
@@ -232,61 +436,379 @@ impl OxAgAudioTool {
     ///
     /// If it's a [Event::Moved] it will detect whether the [TileType] on which the robot is on changed
     /// and if it did it plays the configured sound for it, stopping the one playing previously for the different [TileType].
+    /// The switch can be delayed with [OxAgAudioTool::set_tile_debounce] and [OxAgAudioTool::set_min_loop_dwell].
+    /// It also plays the loop configured for the [Content] of the tile, see [OxAgAudioTool::set_content_sound].
     ///
     /// If it's either a [Event::TimeChanged] or a [Event::DayChanged] it will detect whether the [WeatherType] has changed
     /// and if it did it plays the configured sound for it, stopping the one playing previously for the different [WeatherType].
     ///
-    /// If it's any other event, it checks whether a sound is configured for it and plays it
+    /// In both cases, if the new sound uses the same audio file as the one currently playing, the loop is not restarted,
+    /// see [OxAgAudioTool::set_shared_asset_tween]. Otherwise the new loop starts as configured by its
+    /// [OxAgLoopStart](sound_config::OxAgLoopStart).
+    ///
+    /// If it's a [Event::TileContentUpdated] it plays the sound configured for the transition from the last known [Content]
+    /// at those coordinates to the new one, see [OxAgAudioTool::set_content_transition_sound].
+    /// If no transition matches, the sound configured for the event is played instead.
+    ///
+    /// If it's any other event, it checks whether a sound is configured for it and plays it.
+    /// Events carrying an amount play it scaled as configured by its [OxAgAmountScaling](amount_scaling::OxAgAmountScaling)
+    ///
+    /// [Event::EnergyRecharged] and [Event::EnergyConsumed] also update the tracked energy level, see [OxAgAudioTool::set_low_energy_warning]
+    ///
+    /// The commands sent by the [OxAgAudioController]s get processed first, see [OxAgAudioTool::process_commands]
     pub fn play_audio_based_on_event(
         &mut self,
         event: &Event,
     ) -> Result<(), OxAgAudioToolError> {
-        let event_sound_data = self.event_to_sound_data.get(event_key(event)).cloned();
+        self.process_commands();
+        self.handle_event(event)
+    }
+
+    /// Advances the timers of the tool: the [TileType] debounce and the minimum loop dwell
+    /// (a loop switch they held back happens as soon as it's allowed), the decay of the adaptive music activity,
+    /// the ambient one-shots and the playlist
+    ///
+    /// It's meant to be called from `process_tick`, so that the tool keeps on changing between two events.
+    /// It also processes the commands sent by the [OxAgAudioController]s, see [OxAgAudioTool::process_commands].
+    ///
+    /// # Params
+    /// - elapsed - the time passed since the last call, only used with [OxAgTimeSource::Manual], see [OxAgAudioTool::set_time_source]
+    pub fn update(&mut self, elapsed: Duration) -> Result<(), OxAgAudioToolError> {
+        self.clock.advance(elapsed);
+        let now = self.clock.now();
+
+        self.process_commands();
+
+        if let Some(tile_type) = self.tile_type {
+            let current_tile_type = self.current_tile_sound.as_ref().map(|playing| &playing.key);
+
+            if self.tile_debounce.is_settled(&tile_type, current_tile_type, now) {
+                self.switch_tile_loop(tile_type, now)?;
+            }
+        }
+
+        if let Some(weather_type) = self.weather_type.clone() {
+            self.switch_weather_loop(weather_type, now)?;
+        }
+
+        if let Some(music) = self.adaptive_music.as_mut() {
+            music.update(self.energy_monitor.depletion(), now)?;
+        }
+
+        // the one-shots would all start together once resumed
+        if !self.paused {
+            if let Some(duration) = self.tile_ambience.update(&mut *lock(&self.audio_manager)?, &mut self.rng, now)? {
+                self.mixer.duck(OxAgSoundCategory::Tile, &self.timeline, duration)?;
+            }
+
+            if let Some(duration) = self.weather_ambience.update(&mut *lock(&self.audio_manager)?, &mut self.rng, now)? {
+                self.mixer.duck(OxAgSoundCategory::Weather, &self.timeline, duration)?;
+            }
+        }
+
+        self.update_schedules()
+    }
+
+    /// Sets where the time of the timers of the tool comes from, see [OxAgTimeSource]
+    ///
+    /// The timers are the [TileType] debounce, the minimum loop dwell and the decay of the adaptive music activity.
+    /// The sounds themselves, the ducking and the playlist always follow the audio.
+    pub fn set_time_source(&mut self, source: OxAgTimeSource) {
+        self.clock.set_source(source);
+    }
+
+    /// Seeds the random choices of the ambient one-shots: when they are played, which sound, its panning and its volume,
+    /// and the position the loops with [OxAgLoopStart::Random](sound_config::OxAgLoopStart::Random) start at
+    ///
+    /// Together with [OxAgTimeSource::Manual] the same events and updates play the same one-shots on every run
+    pub fn set_random_seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    /// Returns a handle that sends commands to the tool from any thread, see [OxAgAudioController]
+    pub fn controller(&self) -> OxAgAudioController {
+        OxAgAudioController::new(self.command_sender.clone())
+    }
 
-        if let Some(data) = event_sound_data {
-            self.audio_manager.play(data)?;
+    /// Executes, in order, the commands sent by the [OxAgAudioController]s
+    ///
+    /// It gets called every time an event is processed and on every [OxAgAudioTool::update],
+    /// call it to react to the commands in between
+    ///
+    /// # Errors
+    /// A command that fails doesn't stop the following ones, nor the event or the update that processes it.
+    /// Its error gets queued instead, see [OxAgAudioTool::pop_command_error]
+    pub fn process_commands(&mut self) {
+        while let Ok(command) = self.commands.try_recv() {
+            if let Err(e) = self.execute_command(command) {
+                self.command_errors.push_back(e);
+            }
         }
+    }
+
+    /// Returns the oldest error of a command sent by an [OxAgAudioController] that wasn't returned yet, if any
+    pub fn pop_command_error(&mut self) -> Option<OxAgAudioToolError> {
+        self.command_errors.pop_front()
+    }
+
+    /// Executes a command sent by an [OxAgAudioController]
+    fn execute_command(&mut self, command: OxAgAudioCommand) -> Result<(), OxAgAudioToolError> {
+        match command {
+            OxAgAudioCommand::PlayAudio(sound_config) => self.play_audio(&sound_config)?,
+            OxAgAudioCommand::Event(event) => self.handle_event(&event)?,
+            OxAgAudioCommand::SetMasterVolume { amplitude, tween } => self.set_master_volume(amplitude, tween)?,
+            OxAgAudioCommand::SetMuted { muted, tween } => self.set_muted(muted, tween)?,
+            OxAgAudioCommand::Pause(tween) => self.pause(tween)?,
+            OxAgAudioCommand::Resume(tween) => self.resume(tween)?,
+            OxAgAudioCommand::StopAll(tween) => self.stop_all(tween),
+            OxAgAudioCommand::StopCategory(category, tween) => self.stop_category(category, tween),
+            OxAgAudioCommand::PlayBackgroundMusic(sound_config) => self.play_background_music(&sound_config)?,
+            OxAgAudioCommand::StopBackgroundMusic(tween) => self.stop_background_music(tween),
+            OxAgAudioCommand::StopPlaylist(tween) => self.stop_playlist(tween),
+            OxAgAudioCommand::SkipTrack => self.skip_track()?,
+        }
+
+        Ok(())
+    }
+
+    /// Sets the volume of everything the tool plays to the provided amplitude
+    ///
+    /// # Side effects
+    /// While the tool is muted, the volume is only applied once it gets unmuted
+    pub fn set_master_volume(&mut self, amplitude: f64, tween: Tween) -> Result<(), OxAgAudioToolError> {
+        self.master_volume = amplitude;
+        self.update_master_volume(tween)
+    }
+
+    /// Sets the volume of everything the tool plays to the provided volume in decibels, 0 being the unchanged volume
+    ///
+    /// # Side effects
+    /// While the tool is muted, the volume is only applied once it gets unmuted
+    pub fn set_master_volume_db(&mut self, decibels: f64, tween: Tween) -> Result<(), OxAgAudioToolError> {
+        self.set_master_volume(Volume::Decibels(decibels).as_amplitude(), tween)
+    }
+
+    /// Returns the master volume as an amplitude, see [OxAgAudioTool::set_master_volume]
+    pub fn master_volume(&self) -> f64 {
+        self.master_volume
+    }
+
+    /// Silences everything the tool plays, without stopping anything
+    pub fn set_muted(&mut self, muted: bool, tween: Tween) -> Result<(), OxAgAudioToolError> {
+        self.muted = muted;
+        self.update_master_volume(tween)
+    }
+
+    /// Fades out and pauses everything the tool plays, e.g. when the game gets paused, the sounds and the music carry on from where they were once resumed
+    ///
+    /// # Side effects
+    /// The output track of the tool fades to silence and its clocks get paused, so that the scheduled sounds and the ducking wait for the resume.
+    /// The ambient one-shots are not played while paused and the sounds started while paused are not heard until resumed.
+    ///
+    /// The whole audio manager gets paused only if the tool created it,
+    /// the other users of a shared one (see [OxAgAudioToolBuilder::with_audio_manager]) carry on playing
+    pub fn pause(&mut self, tween: Tween) -> Result<(), OxAgAudioToolError> {
+        if self.paused {
+            return Ok(());
+        }
+
+        self.output.set_volume(Volume::Amplitude(0.0), tween)?;
+
+        for handle in self.sound_handles() {
+            let _ = handle.pause(tween);
+        }
+
+        self.tile_ambience.pause(tween);
+        self.weather_ambience.pause(tween);
+        self.energy_monitor.pause_warning(tween);
+
+        if let Some(playlist) = self.playlist.as_mut() {
+            playlist.pause(tween);
+        }
+
+        if let Some(music) = self.adaptive_music.as_mut() {
+            music.pause(tween);
+        }
+
+        if let Some(music) = self.sequenced_music.as_mut() {
+            music.pause(tween)?;
+        }
+
+        self.timeline.pause()?;
+
+        if self.owns_audio_manager {
+            lock(&self.audio_manager)?.pause(tween)?;
+        }
+
+        self.paused = true;
+
+        Ok(())
+    }
+
+    /// Fades the audio paused by [OxAgAudioTool::pause] back in
+    pub fn resume(&mut self, tween: Tween) -> Result<(), OxAgAudioToolError> {
+        if !self.paused {
+            return Ok(());
+        }
+
+        if self.owns_audio_manager {
+            lock(&self.audio_manager)?.resume(tween)?;
+        }
+
+        self.timeline.start()?;
+
+        if let Some(music) = self.sequenced_music.as_mut() {
+            music.resume(tween)?;
+        }
+
+        if let Some(music) = self.adaptive_music.as_mut() {
+            music.resume(tween);
+        }
+
+        if let Some(playlist) = self.playlist.as_mut() {
+            playlist.resume(&self.timeline, tween);
+        }
+
+        self.energy_monitor.resume_warning(tween);
+        self.weather_ambience.resume(tween);
+        self.tile_ambience.resume(tween);
+
+        for handle in self.sound_handles() {
+            let _ = handle.unpause(tween);
+        }
+
+        self.output.set_volume(Volume::Amplitude(1.0), tween)?;
+        self.paused = false;
+
+        Ok(())
+    }
+
+    /// Returns whether the audio is paused, see [OxAgAudioTool::pause]
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Fades out and stops everything the tool plays, see [OxAgAudioTool::stop_category]
+    pub fn stop_all(&mut self, tween: Tween) {
+        for category in OxAgSoundCategory::ALL {
+            self.stop_category(category, tween);
+        }
+    }
+
+    /// Fades out and stops all the sounds of the category
+    ///
+    /// # Side effects
+    /// - [OxAgSoundCategory::Event] - the event one-shots and the low energy warning, which starts again with the next energy [Event]
+    /// - [OxAgSoundCategory::Tile] - the [TileType] loop and its one-shots, which start again with the next [Event::Moved]
+    /// - [OxAgSoundCategory::Weather] - the [WeatherType] loop and its one-shots, which start again with the next weather [Event]
+    /// - [OxAgSoundCategory::Content] - the [Content] loops, which start again with the next [Event::Moved]
+    /// - [OxAgSoundCategory::Music] - the background music, the playlist, the adaptive and the sequenced music
+    pub fn stop_category(&mut self, category: OxAgSoundCategory, tween: Tween) {
+        match category {
+            OxAgSoundCategory::Event => {
+                for mut one_shot in self.event_sounds.drain(..) {
+                    let _ = one_shot.handle.stop(tween);
+                }
+
+                self.energy_monitor.stop_warning(tween);
+            }
+            OxAgSoundCategory::Tile => {
+                if let Some(playing) = self.current_tile_sound.take() {
+                    self.loop_switcher.stop_with(playing, tween);
+                }
+
+                self.tile_type = None;
+                self.tile_debounce.reset();
+                self.tile_ambience.stop(tween);
+            }
+            OxAgSoundCategory::Weather => {
+                if let Some(playing) = self.current_weather_sound.take() {
+                    self.loop_switcher.stop_with(playing, tween);
+                }
+
+                self.weather_type = None;
+                self.weather_ambience.stop(tween);
+            }
+            OxAgSoundCategory::Content => {
+                for playing in self.current_content_sounds.drain(..) {
+                    self.loop_switcher.stop_with(playing, tween);
+                }
+            }
+            OxAgSoundCategory::Music => {
+                self.stop_background_music(tween);
+                self.stop_playlist(tween);
+                self.stop_adaptive_music(tween);
+                self.stop_sequenced_music(tween);
+            }
+        }
+    }
+
+    /// Reacts to the event, see [OxAgAudioTool::play_audio_based_on_event]
+    fn handle_event(&mut self, event: &Event) -> Result<(), OxAgAudioToolError> {
+        let transition_sound = match event {
+            Event::TileContentUpdated(tile, coordinates) => self.content_transitions.transition(*coordinates, &tile.content),
+            _ => None,
+        };
+
+        match transition_sound {
+            Some((key, sound)) => {
+                let handle = sound.source.play(&mut *lock(&self.audio_manager)?, |settings| settings)?;
+                track_one_shot(&mut self.event_sounds, Some(key), handle);
+
+                if sound.ducks {
+                    self.mixer.duck(OxAgSoundCategory::Event, &self.timeline, sound.source.duration())?;
+                }
+            }
+            None => self
+                .play_event_sound(event)
+                .map_err(|e| e.context("playing the sound", Some(OxAgMappingKey::from(event))))?,
+        }
+
+        if self.energy_monitor.track(event) {
+            self.energy_monitor.update_warning(&mut *lock(&self.audio_manager)?)?;
+        }
+
+        let now = self.clock.now();
+
+        if let Some(music) = self.adaptive_music.as_mut() {
+            music.observe(event, now);
+            music.update(self.energy_monitor.depletion(), now)?;
+        }
+
+        if let Some(music) = self.sequenced_music.as_mut() {
+            music.observe(event);
+            music.update(&mut *lock(&self.audio_manager)?)?;
+        }
+
+        self.update_schedules()?;
 
         match event {
-            Event::Moved(tile, _) => {
+            Event::Moved(tile, coordinates) => {
+                self.content_transitions.remember(*coordinates, &tile.content);
+
                 let tile_type = tile.tile_type;
+                let current_tile_type = self.current_tile_sound.as_ref().map(|playing| &playing.key);
+                self.tile_type = Some(tile_type);
 
-                if self.current_tile_sound.is_none() || tile_type != self.current_tile_sound.as_ref().unwrap().0 {
-                    if let Some(previous_tile_sound) = &mut self.current_tile_sound {
-                        let _ = previous_tile_sound.1.stop(Tween::default());
-                    }
-
-                    let sound_data = self.tile_type_to_sound_data.get(&tile_type);
-
-                    if let Some(sound_data) = sound_data {
-                        let sound_handle = self
-                            .audio_manager
-                            .play::<StaticSoundData>(sound_data.clone())?;
-                        self.current_tile_sound = Some((tile_type, sound_handle));
-                    } else {
-                        self.current_tile_sound = None;
-                    }
+                if self.tile_debounce.settle(tile_type, current_tile_type, now) {
+                    self.switch_tile_loop(tile_type, now)?;
+                }
+
+                if !self.content_from_surroundings {
+                    let contents = HashSet::from([content_key(&tile.content).to_string()]);
+                    self.update_content_sounds(contents)?;
                 }
             }
             Event::TimeChanged(environmental_conditions) | Event::DayChanged(environmental_conditions) => {
                 let weather_type = environmental_conditions.get_weather_condition();
+                self.weather_type = Some(weather_type.clone());
+                self.weather_ambience.activate(Some(weather_type.clone()), &mut self.rng, now);
 
-                if self.current_weather_sound.is_none() || weather_type != self.current_weather_sound.as_ref().unwrap().0 {
-                    if let Some(previous_weather_sound) = &mut self.current_weather_sound {
-                        let _ = previous_weather_sound.1.stop(Tween::default());
-                    }
-
-                    let sound_data = self.weather_type_to_sound_data.get(&weather_type);
-
-                    if let Some(sound_data) = sound_data {
-                        let sound_handle = self
-                            .audio_manager
-                            .play::<StaticSoundData>(sound_data.clone())?;
-                        self.current_weather_sound = Some((weather_type, sound_handle));
-                    } else {
-                        self.current_weather_sound = None;
-                    }
+                if let Some(tween) = self.mixer.environment.enter_weather(weather_type.clone()) {
+                    self.mixer.update_effects(tween)?;
                 }
+
+                self.switch_weather_loop(weather_type, now)?;
             }
             _ => {}
         };
@@ -299,10 +821,707 @@ impl OxAgAudioTool {
         &mut self,
         sound_config: &OxAgSoundConfig,
     ) -> Result<(), OxAgAudioToolError> {
-        let sound_data = sound_config.to_sound_data()?;
+        let mut sound = self.mixer.load(OxAgSoundCategory::Event, sound_config)?;
+        self.loudness.normalize(OxAgSoundCategory::Event, &mut sound);
+        let handle = sound.source.play(&mut *lock(&self.audio_manager)?, |settings| settings)?;
+        track_one_shot(&mut self.event_sounds, None, handle);
+
+        if sound.ducks {
+            self.mixer.duck(OxAgSoundCategory::Event, &self.timeline, sound.source.duration())?;
+        }
+
+        Ok(())
+    }
+
+    /// When the robot moves between two [TileType]s (or the weather changes between two [WeatherType]s)
+    /// whose sounds share the same audio file, the loop keeps on playing instead of restarting.
+    ///
+    /// # Params
+    /// - tween - if [Some], the volume, playback rate and panning of the loop get smoothly adjusted
+    ///   to the settings of the new sound with the given [Tween], otherwise the loop is left untouched
+    pub fn set_shared_asset_tween(&mut self, tween: Option<Tween>) {
+        self.loop_switcher.shared_asset_tween = tween;
+    }
+
+    /// Delays switching the [TileType] loop until the robot has been on the new [TileType] for a while,
+    /// so that walking along the border between two [TileType]s doesn't swap loops on every step
+    ///
+    /// # Params
+    /// - min_steps - the amount of consecutive [Event::Moved] on the new [TileType] before switching
+    /// - min_time - the minimum amount of time spent on the new [TileType] before switching
+    pub fn set_tile_debounce(&mut self, min_steps: usize, min_time: Duration) {
+        self.tile_debounce.min_steps = min_steps;
+        self.tile_debounce.min_time = min_time;
+    }
+
+    /// Sets the minimum amount of time a [TileType] or [WeatherType] loop plays before it can be replaced by another one
+    pub fn set_min_loop_dwell(&mut self, min_dwell: Duration) {
+        self.loop_switcher.min_dwell = min_dwell;
+    }
+
+    /// Sets how long a [TileType], [WeatherType] or [Content] loop takes to fade out while the one replacing it fades in
+    ///
+    /// With a crossfade of 0 (the default) the new loop starts right away
+    pub fn set_loop_crossfade(&mut self, crossfade: Duration) {
+        self.loop_switcher.crossfade = crossfade;
+    }
+
+    /// Configures the sporadic one-shots played while the robot is on the [TileType], replacing the previous ones
+    ///
+    /// The one-shots are played by [OxAgAudioTool::update] and the ones still playing get cancelled when the robot leaves the [TileType].
+    /// Like the loop, they follow the [TileType] debounce, see [OxAgAudioTool::set_tile_debounce]
+    pub fn set_tile_one_shots(
+        &mut self,
+        tile_type: TileType,
+        one_shots: &OxAgAmbientOneShots,
+    ) -> Result<(), OxAgAudioToolError> {
+        let key = OxAgMappingKey::TileType(tile_type);
+        let loaded = self.load_one_shots(OxAgSoundCategory::Tile, key, one_shots)?;
+        self.tile_ambience.set(tile_type, loaded, &mut self.rng, self.clock.now());
+
+        Ok(())
+    }
+
+    /// Configures the sporadic one-shots played while the weather is the [WeatherType], replacing the previous ones
+    ///
+    /// The one-shots are played by [OxAgAudioTool::update] and the ones still playing get cancelled when the weather changes
+    pub fn set_weather_one_shots(
+        &mut self,
+        weather_type: WeatherType,
+        one_shots: &OxAgAmbientOneShots,
+    ) -> Result<(), OxAgAudioToolError> {
+        let key = OxAgMappingKey::WeatherType(weather_type.clone());
+        let loaded = self.load_one_shots(OxAgSoundCategory::Weather, key, one_shots)?;
+        self.weather_ambience.set(weather_type, loaded, &mut self.rng, self.clock.now());
+
+        Ok(())
+    }
+
+    /// Configures the ambience loop played while the robot is on (or next to) a tile with the given [Content],
+    /// independently of the [TileType] loop
+    ///
+    /// # Params
+    /// - content - the [Content] the sound is played for, its properties are ignored
+    /// - sound_config - the [OxAgSoundConfig] of the sound, you probably want it to be looped
+    pub fn set_content_sound(
+        &mut self,
+        content: &Content,
+        sound_config: &OxAgSoundConfig,
+    ) -> Result<(), OxAgAudioToolError> {
+        let mut sound = self
+            .mixer
+            .load(OxAgSoundCategory::Content, sound_config)
+            .map_err(|e| e.context("loading the sound", Some(OxAgMappingKey::from(content))))?;
+        self.loudness.normalize(OxAgSoundCategory::Content, &mut sound);
+        self.content_to_sound_data.insert(content_key(content).to_string(), sound);
+
+        Ok(())
+    }
+
+    /// Plays the [Content] loops for all the tiles around the robot
+    ///
+    /// Once this gets called the [Content] loops follow only the given surroundings instead of the tile in [Event::Moved],
+    /// so it should be called every time the robot looks around, e.g. with the result of `robot_view` in `process_tick`.
+    ///
+    /// # Params
+    /// - surroundings - the tiles around the robot, [None] tiles are ignored
+    pub fn play_audio_based_on_surroundings(
+        &mut self,
+        surroundings: &[Vec<Option<Tile>>],
+    ) -> Result<(), OxAgAudioToolError> {
+        self.content_from_surroundings = true;
+
+        let contents = surroundings
+            .iter()
+            .flatten()
+            .flatten()
+            .map(|tile| content_key(&tile.content).to_string())
+            .collect();
+
+        self.update_content_sounds(contents)
+    }
+
+    /// Configures the sound played when the [Content] of a tile changes, e.g. from [Content::Fire] to [Content::None]
+    /// when a fire gets extinguished
+    ///
+    /// The tool remembers the last known [Content] of each tile the robot moved on or that got updated,
+    /// and compares it with the one in [Event::TileContentUpdated].
+    /// Only the tiles within 32 tiles of the robot are remembered, a tile farther away only matches [OxAgContentMatch::Any] as the old content.
+    ///
+    /// # Params
+    /// - from - the [Content] before the update
+    /// - to - the [Content] after the update
+    /// - sound_config - the [OxAgSoundConfig] of the sound
+    pub fn set_content_transition_sound(
+        &mut self,
+        from: OxAgContentMatch,
+        to: OxAgContentMatch,
+        sound_config: &OxAgSoundConfig,
+    ) -> Result<(), OxAgAudioToolError> {
+        let mut sound = self
+            .mixer
+            .load(OxAgSoundCategory::Event, sound_config)
+            .map_err(|e| e.context("loading the sound", Some(ContentTransitions::key(&from, &to))))?;
+        self.loudness.normalize(OxAgSoundCategory::Event, &mut sound);
+        self.content_transitions.insert(&from, &to, sound);
+
+        Ok(())
+    }
+
+    /// Configures the warning loop played while the tracked energy level of the robot is low
+    ///
+    /// The energy level is tracked by accumulating [Event::EnergyRecharged] and [Event::EnergyConsumed],
+    /// starting from [DEFAULT_ENERGY_LEVEL](energy_monitor::DEFAULT_ENERGY_LEVEL), see [OxAgAudioTool::sync_energy] to keep it accurate.
+    pub fn set_low_energy_warning(
+        &mut self,
+        warning: &OxAgLowEnergyWarning,
+    ) -> Result<(), OxAgAudioToolError> {
+        let mut sound = self
+            .mixer
+            .load(OxAgSoundCategory::Event, &warning.sound_config)
+            .map_err(|e| e.context("loading the sound", Some(OxAgMappingKey::LowEnergyWarning)))?;
+        self.loudness.normalize(OxAgSoundCategory::Event, &mut sound);
+        self.energy_monitor.set_warning(warning, sound);
+        self.energy_monitor.update_warning(&mut *lock(&self.audio_manager)?)
+    }
+
+    /// Returns the tracked energy level of the robot
+    pub fn energy_level(&self) -> usize {
+        self.energy_monitor.level
+    }
+
+    /// Overrides the tracked energy level of the robot
+    pub fn set_energy_level(&mut self, level: usize) -> Result<(), OxAgAudioToolError> {
+        self.energy_monitor.level = level;
+        self.energy_monitor.update_warning(&mut *lock(&self.audio_manager)?)
+    }
+
+    /// Resyncs the tracked energy level with the actual [Energy] of the robot
+    pub fn sync_energy(&mut self, energy: &Energy) -> Result<(), OxAgAudioToolError> {
+        self.set_energy_level(energy.get_energy_level())
+    }
+
+    /// Starts an adaptive music track, whose stems fade in and out following the state of the game,
+    /// see [OxAgAdaptiveMusic]
+    ///
+    /// The adaptive music playing previously gets stopped
+    pub fn play_adaptive_music(
+        &mut self,
+        music: &OxAgAdaptiveMusic,
+    ) -> Result<(), OxAgAudioToolError> {
+        self.stop_adaptive_music(music.tween);
+
+        let mut music = AdaptiveMusic::play(
+            &mut *lock(&self.audio_manager)?,
+            &self.timeline,
+            self.mixer.destination(OxAgSoundCategory::Music),
+            music,
+            self.tile_type,
+            self.weather_type.clone(),
+        )?;
+        music.update(self.energy_monitor.depletion(), self.clock.now())?;
+        self.adaptive_music = Some(music);
+
+        Ok(())
+    }
+
+    /// Fades out and stops the adaptive music, if any
+    pub fn stop_adaptive_music(&mut self, tween: Tween) {
+        if let Some(mut music) = self.adaptive_music.take() {
+            music.stop(tween);
+        }
+    }
+
+    /// Starts a sequenced music track, whose sections follow the [TileType] the robot is on and the [WeatherType],
+    /// changing on the next beat or bar, see [OxAgSequencedMusic]
+    ///
+    /// The default section, if any, starts right away, otherwise the first section starts as soon as the robot moves or the time changes.
+    /// The sequenced music playing previously gets stopped
+    pub fn play_sequenced_music(
+        &mut self,
+        music: &OxAgSequencedMusic,
+    ) -> Result<(), OxAgAudioToolError> {
+        self.stop_sequenced_music(Tween::default());
+
+        let mut music = SequencedMusic::new(
+            &mut *lock(&self.audio_manager)?,
+            self.mixer.destination(OxAgSoundCategory::Music),
+            music,
+            self.tile_type,
+            self.weather_type.clone(),
+        )?;
+        music.update(&mut *lock(&self.audio_manager)?)?;
+        self.sequenced_music = Some(music);
+
+        Ok(())
+    }
+
+    /// Fades out and stops the sequenced music, if any
+    pub fn stop_sequenced_music(&mut self, tween: Tween) {
+        if let Some(mut music) = self.sequenced_music.take() {
+            music.stop(tween);
+        }
+    }
+
+    /// Sets how the other [OxAgSoundCategory]s get ducked while a sound that [ducks](OxAgSoundConfig::ducks) is playing,
+    /// see [OxAgDucking]
+    pub fn set_ducking(&mut self, ducking: OxAgDucking) {
+        self.mixer.ducking = ducking;
+    }
+
+    /// Applies an effect chain while the robot is on the given [TileType], see [OxAgEffectChain]
+    ///
+    /// The chain follows the [TileType] loop, so it's held back by the same debounce, see [OxAgAudioTool::set_tile_debounce]
+    pub fn set_tile_effects(
+        &mut self,
+        tile_type: TileType,
+        chain: OxAgEffectChain,
+    ) -> Result<(), OxAgAudioToolError> {
+        match self.mixer.environment.set_tile_chain(tile_type, chain) {
+            Some(tween) => self.mixer.update_effects(tween),
+            None => Ok(()),
+        }
+    }
+
+    /// Applies an effect chain while the weather is the given [WeatherType], see [OxAgEffectChain]
+    pub fn set_weather_effects(
+        &mut self,
+        weather_type: WeatherType,
+        chain: OxAgEffectChain,
+    ) -> Result<(), OxAgAudioToolError> {
+        match self.mixer.environment.set_weather_chain(weather_type, chain) {
+            Some(tween) => self.mixer.update_effects(tween),
+            None => Ok(()),
+        }
+    }
+
+    /// Sets the limiter on the master bus, see [OxAgLimiter], [None] removes it
+    pub fn set_master_limiter(&mut self, limiter: Option<OxAgLimiter>) -> Result<(), OxAgAudioToolError> {
+        self.master_bus.set_limiter(limiter)
+    }
+
+    /// Returns how many frames clipped on the master bus, after the limiter, since the tool was created
+    /// or since the last call to [OxAgAudioTool::reset_clipped_frames]
+    ///
+    /// A growing amount means the mix is too loud
+    pub fn clipped_frames(&self) -> u64 {
+        self.master_bus.clipped_frames()
+    }
+
+    /// Resets the amount of clipped frames to 0
+    pub fn reset_clipped_frames(&mut self) {
+        self.master_bus.reset_clipped_frames();
+    }
+
+    /// Sets how the loudness of the sounds gets normalized, see [OxAgLoudnessNormalization], [None] restores the configured volumes
+    ///
+    /// The normalization applies right away to the [Event], [TileType], [WeatherType] and [Content] sounds,
+    /// to the content transitions and the low energy warning,
+    /// and to the sounds loaded afterwards with [OxAgAudioTool::set_content_sound] and [OxAgAudioTool::play_audio].
+    /// Loops that are already playing keep their volume until they get restarted
+    pub fn set_loudness_normalization(&mut self, normalization: Option<OxAgLoudnessNormalization>) {
+        self.loudness.set_normalization(normalization);
+
+        let sounds = self.event_to_sound_data.values_mut().map(|sound| (OxAgSoundCategory::Event, sound))
+            .chain(self.tile_type_to_sound_data.values_mut().map(|sound| (OxAgSoundCategory::Tile, sound)))
+            .chain(self.weather_type_to_sound_data.values_mut().map(|sound| (OxAgSoundCategory::Weather, sound)))
+            .chain(self.content_to_sound_data.values_mut().map(|sound| (OxAgSoundCategory::Content, sound)))
+            .chain(self.tile_ambience.one_shots.values_mut().flat_map(|one_shots| one_shots.sounds.iter_mut()).map(|sound| (OxAgSoundCategory::Tile, sound)))
+            .chain(self.weather_ambience.one_shots.values_mut().flat_map(|one_shots| one_shots.sounds.iter_mut()).map(|sound| (OxAgSoundCategory::Weather, sound)))
+            .chain(self.content_transitions.sounds_mut().map(|sound| (OxAgSoundCategory::Event, sound)))
+            .chain(self.energy_monitor.warning_sound_mut().map(|sound| (OxAgSoundCategory::Event, sound)));
+
+        for (category, sound) in sounds {
+            self.loudness.normalize(category, sound);
+        }
+    }
+
+    /// Returns the loudness measured for each audio file, in the unit of the [OxAgLoudnessMeasure](loudness::OxAgLoudnessMeasure)
+    ///
+    /// Only the files normalized with [OxAgAudioTool::set_loudness_normalization] get measured, silent files are never reported
+    pub fn measured_loudness(&self) -> &HashMap<String, f64> {
+        &self.loudness.measured
+    }
+
+    /// Loops the sound in the background, on the music track
+    ///
+    /// The background music playing previously gets stopped
+    pub fn play_background_music(
+        &mut self,
+        sound_config: &OxAgSoundConfig,
+    ) -> Result<(), OxAgAudioToolError> {
+        self.stop_background_music(Tween::default());
 
-        self.audio_manager.play(sound_data)?;
+        let mut routed = self.mixer.route(OxAgSoundCategory::Music, sound_config);
+        routed.looped();
+
+        let source = routed
+            .to_sound_source()
+            .map_err(|e| e.context("loading the sound", Some(OxAgMappingKey::BackgroundMusic)))?;
+        let handle = source
+            .play(&mut *lock(&self.audio_manager)?, |settings| settings)
+            .map_err(|e| e.context("playing the sound", Some(OxAgMappingKey::BackgroundMusic)))?;
+        self.background_music = Some((sound_config.clone(), handle));
 
         Ok(())
     }
-}
\ No newline at end of file
+
+    /// Fades out and stops the background music, if any
+    pub fn stop_background_music(&mut self, tween: Tween) {
+        if let Some((_, mut handle)) = self.background_music.take() {
+            let _ = handle.stop(tween);
+        }
+    }
+
+    /// Returns why no sound is heard, [None] if the audio device is playing
+    ///
+    /// The tool only goes silent if it was built with [OxAgAudioToolBuilder::silent_fallback]
+    ///
+    /// # Errors
+    /// [CommandError::MutexPoisoned](kira::CommandError::MutexPoisoned) if the audio thread panicked while recording the reason
+    pub fn silence_reason(&self) -> Result<Option<String>, OxAgAudioToolError> {
+        Ok(lock(&self.silence_reason)?.clone())
+    }
+
+    /// Returns the [TileType] whose loop is playing, [None] if there's no loop
+    ///
+    /// It can differ from the [TileType] the robot is on while the switch is held back by the debounce or the minimum loop dwell,
+    /// or when two [TileType]s share the same loop
+    pub fn current_tile_type(&self) -> Option<TileType> {
+        self.current_tile_sound.as_ref().map(|playing| playing.key)
+    }
+
+    /// Returns the [WeatherType] whose loop is playing, [None] if there's no loop
+    pub fn current_weather_type(&self) -> Option<WeatherType> {
+        self.current_weather_sound.as_ref().map(|playing| playing.key.clone())
+    }
+
+    /// Returns the [TileType], [WeatherType] and [Content] loops, the background music and the low energy warning
+    /// that are playing, see [OxAgSoundStatus]
+    ///
+    /// The loops fading out after a switch are not included, nor are the playlist, the adaptive and the sequenced music
+    pub fn playing_loops(&self) -> Vec<OxAgSoundStatus> {
+        let status = |key: OxAgMappingKey, category: OxAgSoundCategory, handle: &SoundHandle| OxAgSoundStatus {
+            key: Some(key),
+            category,
+            state: handle.state(),
+            position: handle.position(),
+        };
+
+        let tile = self
+            .current_tile_sound
+            .iter()
+            .map(|playing| status(OxAgMappingKey::TileType(playing.key), OxAgSoundCategory::Tile, &playing.handle));
+        let weather = self
+            .current_weather_sound
+            .iter()
+            .map(|playing| status(OxAgMappingKey::WeatherType(playing.key.clone()), OxAgSoundCategory::Weather, &playing.handle));
+        let content = self
+            .current_content_sounds
+            .iter()
+            .map(|playing| status(OxAgMappingKey::Content(playing.key.clone()), OxAgSoundCategory::Content, &playing.handle));
+        let music = self
+            .background_music
+            .iter()
+            .map(|(_, handle)| status(OxAgMappingKey::BackgroundMusic, OxAgSoundCategory::Music, handle));
+        let warning = self
+            .energy_monitor
+            .warning_handle()
+            .map(|handle| status(OxAgMappingKey::LowEnergyWarning, OxAgSoundCategory::Event, handle));
+
+        tile.chain(weather).chain(content).chain(music).chain(warning).collect()
+    }
+
+    /// Returns the one-shots that are still playing: the [Event] sounds, the ones played with [OxAgAudioTool::play_audio],
+    /// the content transitions and the ambient one-shots, see [OxAgSoundStatus]
+    pub fn playing_one_shots(&self) -> Vec<OxAgSoundStatus> {
+        let status = |key: Option<OxAgMappingKey>, category: OxAgSoundCategory, handle: &SoundHandle| OxAgSoundStatus {
+            key,
+            category,
+            state: handle.state(),
+            position: handle.position(),
+        };
+
+        let events = self
+            .event_sounds
+            .iter()
+            .filter(|one_shot| one_shot.handle.state() != PlaybackState::Stopped)
+            .map(|one_shot| status(one_shot.key.clone(), OxAgSoundCategory::Event, &one_shot.handle));
+        let tile = self
+            .tile_ambience
+            .playing()
+            .map(|(tile_type, handle)| status(Some(OxAgMappingKey::TileType(*tile_type)), OxAgSoundCategory::Tile, handle));
+        let weather = self
+            .weather_ambience
+            .playing()
+            .map(|(weather_type, handle)| status(Some(OxAgMappingKey::WeatherType(weather_type.clone())), OxAgSoundCategory::Weather, handle));
+
+        events.chain(tile).chain(weather).collect()
+    }
+
+    /// Returns the configurations of the sounds mapped to the key, as they were given to the tool, empty if there's none
+    ///
+    /// A [TileType] or a [WeatherType] returns its loop first, followed by its ambient one-shots.
+    /// The configurations can be read with [OxAgSoundConfig::path] and [OxAgSoundConfig::settings].
+    /// [OxAgMappingKey::BackgroundMusic] returns the background music that is playing
+    pub fn mappings(&self, key: &OxAgMappingKey) -> Vec<OxAgSoundConfig> {
+        let configs: Vec<&OxAgSoundConfig> = match key {
+            OxAgMappingKey::Event(event) => self.event_to_sound_data.get(event).map(|sound| &sound.config).into_iter().collect(),
+            OxAgMappingKey::TileType(tile_type) => self
+                .tile_type_to_sound_data
+                .get(tile_type)
+                .map(|sound| &sound.config)
+                .into_iter()
+                .chain(self.tile_ambience.configs(tile_type))
+                .collect(),
+            OxAgMappingKey::WeatherType(weather_type) => self
+                .weather_type_to_sound_data
+                .get(weather_type)
+                .map(|sound| &sound.config)
+                .into_iter()
+                .chain(self.weather_ambience.configs(weather_type))
+                .collect(),
+            OxAgMappingKey::Content(content) => self.content_to_sound_data.get(content).map(|sound| &sound.config).into_iter().collect(),
+            OxAgMappingKey::ContentTransition { from, to } => self.content_transitions.config(from, to).into_iter().collect(),
+            OxAgMappingKey::BackgroundMusic => self.background_music.iter().map(|(sound_config, _)| sound_config).collect(),
+            OxAgMappingKey::LowEnergyWarning => self.energy_monitor.warning_sound().map(|sound| &sound.config).into_iter().collect(),
+        };
+
+        configs.into_iter().cloned().collect()
+    }
+
+    /// Returns the audio manager the tool plays through, e.g. to share it with another tool,
+    /// see [OxAgAudioToolBuilder::with_audio_manager]
+    pub fn audio_manager(&self) -> Arc<Mutex<AudioManager<B>>> {
+        self.audio_manager.clone()
+    }
+
+    /// Returns the track all the sounds of the tool play on, e.g. to route it through the effects of the host application
+    ///
+    /// It's the main track, unless the tool plays through a shared audio manager
+    pub fn output_track(&self) -> TrackId {
+        self.output.id()
+    }
+
+    /// Starts a playlist of background music, see [OxAgPlaylist]
+    ///
+    /// The playlist playing previously gets stopped
+    pub fn play_playlist(
+        &mut self,
+        playlist: &OxAgPlaylist,
+    ) -> Result<(), OxAgAudioToolError> {
+        self.stop_playlist(Tween::default());
+        self.playlist = Some(Playlist::play(&mut *lock(&self.audio_manager)?, &self.timeline, self.mixer.destination(OxAgSoundCategory::Music), playlist)?);
+
+        Ok(())
+    }
+
+    /// Fades out and stops the playlist, if any
+    pub fn stop_playlist(&mut self, tween: Tween) {
+        if let Some(mut playlist) = self.playlist.take() {
+            playlist.stop(tween);
+        }
+    }
+
+    /// Starts the next track of the playlist right away, even if the [OxAgRepeatMode](playlist::OxAgRepeatMode) is [One](playlist::OxAgRepeatMode::One)
+    pub fn skip_track(&mut self) -> Result<(), OxAgAudioToolError> {
+        match self.playlist.as_mut() {
+            Some(playlist) => playlist.skip(&mut *lock(&self.audio_manager)?, &self.timeline),
+            None => Ok(()),
+        }
+    }
+
+    /// Starts the previous track of the playlist right away
+    pub fn previous_track(&mut self) -> Result<(), OxAgAudioToolError> {
+        match self.playlist.as_mut() {
+            Some(playlist) => playlist.previous(&mut *lock(&self.audio_manager)?, &self.timeline),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the oldest [OxAgPlaylistEvent] that wasn't returned yet, if any
+    ///
+    /// # Side effects
+    /// The playlist moves on to the next track if the current one ended
+    pub fn pop_playlist_event(&mut self) -> Result<Option<OxAgPlaylistEvent>, OxAgAudioToolError> {
+        let playlist = match self.playlist.as_mut() {
+            Some(playlist) => playlist,
+            None => return Ok(None),
+        };
+
+        playlist.update(&mut *lock(&self.audio_manager)?, &self.timeline)?;
+
+        Ok(playlist.pop_event())
+    }
+
+    /// Loads the sounds of the one-shots on the track of the category, normalizing their loudness
+    fn load_one_shots(
+        &mut self,
+        category: OxAgSoundCategory,
+        key: OxAgMappingKey,
+        one_shots: &OxAgAmbientOneShots,
+    ) -> Result<LoadedOneShots, OxAgAudioToolError> {
+        let mut sounds = Vec::new();
+
+        for sound_config in one_shots.sounds.iter() {
+            let mut sound = self
+                .mixer
+                .load(category, sound_config)
+                .map_err(|e| e.context("loading the one-shot", Some(key.clone())))?;
+            self.loudness.normalize(category, &mut sound);
+            sounds.push(sound);
+        }
+
+        Ok(LoadedOneShots::new(one_shots, sounds))
+    }
+
+    /// Switches the [TileType] loop to the one of the given [TileType]
+    ///
+    /// The effect chain and the ambient one-shots of the [TileType] follow the same debounced [TileType],
+    /// so they wait too while the minimum loop dwell holds the loop back
+    fn switch_tile_loop(&mut self, tile_type: TileType, now: Duration) -> Result<(), OxAgAudioToolError> {
+        let switched = self
+            .loop_switcher
+            .switch(
+                &mut *lock(&self.audio_manager)?,
+                &mut self.current_tile_sound,
+                tile_type,
+                self.tile_type_to_sound_data.get(&tile_type),
+                &mut self.rng,
+                now,
+            )
+            .map_err(|e| e.context("switching the loop", Some(OxAgMappingKey::TileType(tile_type))))?;
+
+        if !switched {
+            return Ok(());
+        }
+
+        self.tile_ambience.activate(Some(tile_type), &mut self.rng, now);
+
+        if let Some(tween) = self.mixer.environment.enter_tile(tile_type) {
+            self.mixer.update_effects(tween)?;
+        }
+
+        Ok(())
+    }
+
+    /// Switches the [WeatherType] loop to the one of the given [WeatherType]
+    fn switch_weather_loop(&mut self, weather_type: WeatherType, now: Duration) -> Result<(), OxAgAudioToolError> {
+        self.loop_switcher
+            .switch(
+                &mut *lock(&self.audio_manager)?,
+                &mut self.current_weather_sound,
+                weather_type.clone(),
+                self.weather_type_to_sound_data.get(&weather_type),
+                &mut self.rng,
+                now,
+            )
+            .map_err(|e| e.context("switching the loop", Some(OxAgMappingKey::WeatherType(weather_type))))?;
+
+        Ok(())
+    }
+
+    /// Schedules the next tracks of the playlist
+    fn update_schedules(&mut self) -> Result<(), OxAgAudioToolError> {
+        if let Some(playlist) = self.playlist.as_mut() {
+            playlist.update(&mut *lock(&self.audio_manager)?, &self.timeline)?;
+        }
+
+        Ok(())
+    }
+
+    /// Tweens the master volume of the [MasterBus] to the set one, or to silence if muted
+    ///
+    /// The volume of the output track itself is left to [OxAgAudioTool::pause], it's applied after the limiter
+    fn update_master_volume(&mut self, tween: Tween) -> Result<(), OxAgAudioToolError> {
+        let volume = match self.muted {
+            true => Volume::Amplitude(0.0),
+            false => Volume::Amplitude(self.master_volume),
+        };
+
+        Ok(self.master_bus.set_volume(volume, tween)?)
+    }
+
+    /// Returns the handles of the loops, the background music and the event one-shots
+    fn sound_handles(&mut self) -> impl Iterator<Item = &mut SoundHandle> {
+        let tile = self.current_tile_sound.iter_mut().map(|playing| &mut playing.handle);
+        let weather = self.current_weather_sound.iter_mut().map(|playing| &mut playing.handle);
+        let content = self.current_content_sounds.iter_mut().map(|playing| &mut playing.handle);
+
+        tile.chain(weather)
+            .chain(content)
+            .chain(self.background_music.iter_mut().map(|(_, handle)| handle))
+            .chain(self.event_sounds.iter_mut().map(|one_shot| &mut one_shot.handle))
+    }
+
+    /// Plays the sound configured for the event, scaled by the amount it carries
+    fn play_event_sound(&mut self, event: &Event) -> Result<(), OxAgAudioToolError> {
+        let sound = match self.event_to_sound_data.get(event_key(event)) {
+            Some(sound) => sound,
+            None => return Ok(()),
+        };
+
+        let amount = match event_amount(event) {
+            Some(amount) => amount,
+            None => {
+                let handle = sound.source.play(&mut *lock(&self.audio_manager)?, |settings| settings)?;
+                track_one_shot(&mut self.event_sounds, Some(OxAgMappingKey::from(event)), handle);
+
+                if sound.ducks {
+                    self.mixer.duck(OxAgSoundCategory::Event, &self.timeline, sound.source.duration())?;
+                }
+
+                return Ok(());
+            }
+        };
+
+        let scaling = sound.amount_scaling;
+        let settings = scaling.scale_settings(sound.source.settings(), amount);
+        let interval = scaling.repetition_interval.as_millis() as u64;
+        let repetitions = scaling.repetitions_for(amount) as u32;
+        let now = self.timeline.time();
+
+        for repetition in 0..repetitions as u64 {
+            let start_time = match repetition {
+                0 => StartTime::Immediate,
+                _ => StartTime::ClockTime(now + repetition * interval),
+            };
+
+            let handle = sound.source.play(&mut *lock(&self.audio_manager)?, |_| settings.start_time(start_time))?;
+            track_one_shot(&mut self.event_sounds, Some(OxAgMappingKey::from(event)), handle);
+        }
+
+        if sound.ducks && repetitions > 0 {
+            let duration = sound.source.duration() + scaling.repetition_interval * (repetitions - 1);
+            self.mixer.duck(OxAgSoundCategory::Event, &self.timeline, duration)?;
+        }
+
+        Ok(())
+    }
+
+    /// Stops the [Content] loops that are not in `contents` anymore and starts the ones that are missing
+    fn update_content_sounds(&mut self, contents: HashSet<String>) -> Result<(), OxAgAudioToolError> {
+        let (playing, stopped) = self
+            .current_content_sounds
+            .drain(..)
+            .partition(|playing| contents.contains(&playing.key));
+        self.current_content_sounds = playing;
+
+        for playing in stopped {
+            self.loop_switcher.stop(playing);
+        }
+
+        for content in contents {
+            if self.current_content_sounds.iter().any(|playing| playing.key == content) {
+                continue;
+            }
+
+            if let Some(sound) = self.content_to_sound_data.get(&content) {
+                let playing = self.loop_switcher.start(&mut *lock(&self.audio_manager)?, content, sound, &mut self.rng, self.clock.now())?;
+                self.current_content_sounds.push(playing);
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,150 @@
+use crate::error::error::OxAgAudioToolError;
+use kira::clock::clock_info::ClockInfoProvider;
+use kira::dsp::Frame;
+use kira::modulator::value_provider::ModulatorValueProvider;
+use kira::track::effect::compressor::{CompressorBuilder, CompressorHandle};
+use kira::track::effect::volume_control::{VolumeControlBuilder, VolumeControlHandle};
+use kira::track::effect::{Effect, EffectBuilder};
+use kira::track::TrackBuilder;
+use kira::tween::Tween;
+use kira::{CommandError, Volume};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Configures the limiter on the master bus, which turns down the mix when it gets too loud
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OxAgLimiter {
+    pub(crate) threshold: f64,
+    pub(crate) ratio: f64,
+    pub(crate) attack: Duration,
+    pub(crate) release: Duration,
+}
+
+impl Default for OxAgLimiter {
+    fn default() -> Self {
+        OxAgLimiter {
+            threshold: -1.0,
+            ratio: 20.0,
+            attack: Duration::from_millis(1),
+            release: Duration::from_millis(100),
+        }
+    }
+}
+
+impl OxAgLimiter {
+    /// # Params
+    /// - threshold - the volume, in dBFS, above which the mix gets turned down
+    pub fn new(threshold: f64) -> OxAgLimiter {
+        OxAgLimiter {
+            threshold,
+            ..Default::default()
+        }
+    }
+
+    /// Sets how much the volume above the threshold gets reduced, e.g. with a ratio of 4 every 4dB above the threshold become 1dB.<br>
+    /// The default ratio of 20 makes it behave like a limiter, lower ratios like a compressor
+    pub fn ratio(&mut self, ratio: f64) {
+        self.ratio = ratio;
+    }
+
+    /// Sets how quickly the volume gets reduced once the mix goes above the threshold
+    pub fn attack(&mut self, attack: Duration) {
+        self.attack = attack;
+    }
+
+    /// Sets how quickly the volume gets restored once the mix goes back below the threshold
+    pub fn release(&mut self, release: Duration) {
+        self.release = release;
+    }
+}
+
+/// Counts the frames whose samples go beyond full scale
+struct ClipCounter {
+    clipped_frames: Arc<AtomicU64>,
+}
+
+impl Effect for ClipCounter {
+    fn process(
+        &mut self,
+        input: Frame,
+        _dt: f64,
+        _clock_info_provider: &ClockInfoProvider,
+        _modulator_value_provider: &ModulatorValueProvider,
+    ) -> Frame {
+        if input.left.abs() > 1.0 || input.right.abs() > 1.0 {
+            self.clipped_frames.fetch_add(1, Ordering::Relaxed);
+        }
+
+        input
+    }
+}
+
+struct ClipCounterBuilder {
+    clipped_frames: Arc<AtomicU64>,
+}
+
+impl EffectBuilder for ClipCounterBuilder {
+    type Handle = ();
+
+    fn build(self) -> (Box<dyn Effect>, Self::Handle) {
+        (Box::new(ClipCounter { clipped_frames: self.clipped_frames }), ())
+    }
+}
+
+/// The effects on the main track: the master volume, a compressor, neutral until an [OxAgLimiter] is set, and a clip counter
+///
+/// The master volume comes first, so that the limiter and the clip counter see the gain it adds
+pub(crate) struct MasterBus {
+    volume: VolumeControlHandle,
+    compressor: CompressorHandle,
+    clipped_frames: Arc<AtomicU64>,
+}
+
+impl MasterBus {
+    /// Adds the effects to the main track
+    pub(crate) fn add_to(builder: &mut TrackBuilder) -> MasterBus {
+        let clipped_frames = Arc::new(AtomicU64::new(0));
+
+        let volume = builder.add_effect(VolumeControlBuilder::new(Volume::Amplitude(1.0)));
+        let compressor = builder.add_effect(CompressorBuilder::new());
+        builder.add_effect(ClipCounterBuilder { clipped_frames: clipped_frames.clone() });
+
+        MasterBus {
+            volume,
+            compressor,
+            clipped_frames,
+        }
+    }
+
+    /// Tweens the master volume
+    pub(crate) fn set_volume(&mut self, volume: Volume, tween: Tween) -> Result<(), CommandError> {
+        self.volume.set_volume(volume, tween)
+    }
+
+    /// Configures the compressor as the given limiter, [None] making it neutral
+    pub(crate) fn set_limiter(&mut self, limiter: Option<OxAgLimiter>) -> Result<(), OxAgAudioToolError> {
+        let (threshold, ratio) = match limiter {
+            Some(limiter) => {
+                self.compressor.set_attack_duration(limiter.attack, Tween::default())?;
+                self.compressor.set_release_duration(limiter.release, Tween::default())?;
+                (limiter.threshold, limiter.ratio)
+            }
+            None => (0.0, 1.0),
+        };
+
+        self.compressor.set_threshold(threshold, Tween::default())?;
+        self.compressor.set_ratio(ratio, Tween::default())?;
+
+        Ok(())
+    }
+
+    /// Returns the amount of frames that clipped
+    pub(crate) fn clipped_frames(&self) -> u64 {
+        self.clipped_frames.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn reset_clipped_frames(&self) {
+        self.clipped_frames.store(0, Ordering::Relaxed);
+    }
+}
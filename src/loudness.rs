@@ -0,0 +1,254 @@
+use crate::mixer::OxAgSoundCategory;
+use crate::playback::{LoadedSound, SoundSource};
+use kira::dsp::Frame;
+use kira::tween::Value;
+use kira::Volume;
+use std::collections::HashMap;
+use std::f64::consts::PI;
+
+/// How the loudness of a sound is measured
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OxAgLoudnessMeasure {
+    /// The root mean square of the samples, in dBFS<br>
+    /// Quick, but it doesn't account for how the human ear perceives the frequencies
+    Rms,
+    /// The integrated loudness as defined by ITU-R BS.1770, in LUFS<br>
+    /// The samples get K-weighted and the silent parts get gated out
+    #[default]
+    Lufs,
+}
+
+/// The loudness a single sound gets normalized to, see [OxAgSoundConfig::loudness_target](crate::sound_config::OxAgSoundConfig::loudness_target)
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum OxAgLoudnessTarget {
+    /// The target of the [OxAgSoundCategory] the sound is played in
+    #[default]
+    Category,
+    /// The given loudness, in the unit of the [OxAgLoudnessMeasure]
+    Fixed(f64),
+    /// The sound is never normalized
+    Unchanged,
+}
+
+/// Configures the loudness normalization of the decoded sounds
+///
+/// Every sound of a category with a target gets a gain, added to its configured volume, so that its measured loudness matches the target.
+/// Streamed sounds are never normalized, since they aren't decoded in memory.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OxAgLoudnessNormalization {
+    pub(crate) measure: OxAgLoudnessMeasure,
+    pub(crate) targets: HashMap<OxAgSoundCategory, f64>,
+    pub(crate) max_gain: f64,
+}
+
+impl OxAgLoudnessNormalization {
+    /// # Params
+    /// - measure - how the loudness of the sounds is measured, see [OxAgLoudnessMeasure]
+    pub fn new(measure: OxAgLoudnessMeasure) -> OxAgLoudnessNormalization {
+        OxAgLoudnessNormalization {
+            measure,
+            targets: HashMap::new(),
+            max_gain: 12.0,
+        }
+    }
+
+    /// Sets the loudness the sounds of the [OxAgSoundCategory] get normalized to, in the unit of the [OxAgLoudnessMeasure]
+    pub fn target(&mut self, category: OxAgSoundCategory, loudness: f64) {
+        self.targets.insert(category, loudness);
+    }
+
+    /// Sets the maximum gain, in decibels, applied to a quiet sound, so that the noise of a nearly silent file doesn't get blown up
+    pub fn max_gain(&mut self, max_gain: f64) {
+        self.max_gain = max_gain;
+    }
+}
+
+/// Normalizes the loaded sounds, remembering the loudness measured for each asset
+#[derive(Default)]
+pub(crate) struct Loudness {
+    pub(crate) normalization: Option<OxAgLoudnessNormalization>,
+    /// The loudness measured for each asset, in the unit of the [OxAgLoudnessMeasure]
+    pub(crate) measured: HashMap<String, f64>,
+}
+
+impl Loudness {
+    /// Changes the normalization, forgetting the measured loudness if it gets measured differently
+    pub(crate) fn set_normalization(&mut self, normalization: Option<OxAgLoudnessNormalization>) {
+        let measure = |normalization: &Option<OxAgLoudnessNormalization>| normalization.as_ref().map(|normalization| normalization.measure);
+
+        if measure(&normalization) != measure(&self.normalization) {
+            self.measured.clear();
+        }
+
+        self.normalization = normalization;
+    }
+
+    /// Sets the volume of the sound to its configured volume plus the gain that makes it hit its target loudness
+    pub(crate) fn normalize(&mut self, category: OxAgSoundCategory, sound: &mut LoadedSound) {
+        let mut volume = sound.volume;
+
+        if let (Some(gain), Value::Fixed(configured)) = (self.gain(category, sound), sound.volume) {
+            volume = Value::Fixed(Volume::Amplitude(configured.as_amplitude() * Volume::Decibels(gain).as_amplitude()));
+        }
+
+        sound.source.set_volume(volume);
+    }
+
+    /// Returns the gain, in decibels, that makes the sound hit its target loudness, [None] if it doesn't get normalized
+    fn gain(&mut self, category: OxAgSoundCategory, sound: &LoadedSound) -> Option<f64> {
+        let normalization = self.normalization.as_ref()?;
+
+        let target = match sound.loudness_target {
+            OxAgLoudnessTarget::Category => *normalization.targets.get(&category)?,
+            OxAgLoudnessTarget::Fixed(target) => target,
+            OxAgLoudnessTarget::Unchanged => return None,
+        };
+
+        let data = match &sound.source {
+            SoundSource::Static(data) => data,
+            SoundSource::Streaming(..) => return None,
+        };
+
+        let measured = match self.measured.get(&sound.asset) {
+            Some(measured) => *measured,
+            None => {
+                let measured = match normalization.measure {
+                    OxAgLoudnessMeasure::Rms => rms(&data.frames),
+                    OxAgLoudnessMeasure::Lufs => integrated_loudness(&data.frames, data.sample_rate),
+                }?;
+                self.measured.insert(sound.asset.clone(), measured);
+                measured
+            }
+        };
+
+        Some((target - measured).min(normalization.max_gain))
+    }
+}
+
+/// Returns the root mean square of the frames in dBFS, [None] if they are silent
+pub(crate) fn rms(frames: &[Frame]) -> Option<f64> {
+    if frames.is_empty() {
+        return None;
+    }
+
+    let sum: f64 = frames
+        .iter()
+        .map(|frame| (frame.left as f64).powi(2) + (frame.right as f64).powi(2))
+        .sum();
+    let mean_square = sum / (frames.len() * 2) as f64;
+
+    (mean_square > 0.0).then(|| 10.0 * mean_square.log10())
+}
+
+/// A second order IIR filter
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    /// The last two inputs and outputs of each channel
+    state: [[f64; 4]; 2],
+}
+
+impl Biquad {
+    fn new(b: [f64; 3], a: [f64; 3]) -> Biquad {
+        Biquad {
+            b: [b[0] / a[0], b[1] / a[0], b[2] / a[0]],
+            a: [a[1] / a[0], a[2] / a[0]],
+            state: [[0.0; 4]; 2],
+        }
+    }
+
+    /// The high shelf of the K-weighting, modelling the acoustic effect of the head
+    ///
+    /// The coefficients match the ones given by ITU-R BS.1770 at 48 kHz
+    fn high_shelf(sample_rate: f64) -> Biquad {
+        let (gain, q, frequency) = (3.999_843_853_973_347, 0.707_175_236_955_419_6, 1_681.974_450_955_533);
+
+        let k = (PI * frequency / sample_rate).tan();
+        let vh = 10.0_f64.powf(gain / 20.0);
+        let vb = vh.powf(0.499_666_774_154_541_6);
+
+        Biquad::new(
+            [vh + vb * k / q + k * k, 2.0 * (k * k - vh), vh - vb * k / q + k * k],
+            [1.0 + k / q + k * k, 2.0 * (k * k - 1.0), 1.0 - k / q + k * k],
+        )
+    }
+
+    /// The high pass of the K-weighting, ignoring the lowest frequencies
+    ///
+    /// The coefficients match the ones given by ITU-R BS.1770 at 48 kHz
+    fn high_pass(sample_rate: f64) -> Biquad {
+        let (q, frequency) = (0.500_327_037_323_877_3, 38.135_470_876_024_44);
+
+        let k = (PI * frequency / sample_rate).tan();
+        let a0 = 1.0 + k / q + k * k;
+
+        Biquad::new(
+            [a0, -2.0 * a0, a0],
+            [a0, 2.0 * (k * k - 1.0), 1.0 - k / q + k * k],
+        )
+    }
+
+    fn process(&mut self, channel: usize, input: f64) -> f64 {
+        let [x1, x2, y1, y2] = self.state[channel];
+        let output = self.b[0] * input + self.b[1] * x1 + self.b[2] * x2 - self.a[0] * y1 - self.a[1] * y2;
+
+        self.state[channel] = [input, x1, output, y1];
+
+        output
+    }
+}
+
+/// Converts the mean square of a block to LUFS
+fn block_loudness(mean_square: f64) -> f64 {
+    -0.691 + 10.0 * mean_square.log10()
+}
+
+/// Returns the integrated loudness of the frames in LUFS, as defined by ITU-R BS.1770, [None] if they are silent
+///
+/// Frames with the same sample on both channels come from a mono file, so they are measured as a single channel
+pub(crate) fn integrated_loudness(frames: &[Frame], sample_rate: u32) -> Option<f64> {
+    if frames.is_empty() || sample_rate == 0 {
+        return None;
+    }
+
+    let mut high_shelf = Biquad::high_shelf(sample_rate as f64);
+    let mut high_pass = Biquad::high_pass(sample_rate as f64);
+    let channels = match frames.iter().all(|frame| frame.left == frame.right) {
+        true => 1,
+        false => 2,
+    };
+
+    // the K-weighted power of each frame, summed over the channels
+    let powers: Vec<f64> = frames
+        .iter()
+        .map(|frame| {
+            [frame.left, frame.right]
+                .into_iter()
+                .take(channels)
+                .enumerate()
+                .map(|(channel, sample)| high_pass.process(channel, high_shelf.process(channel, sample as f64)).powi(2))
+                .sum()
+        })
+        .collect();
+
+    // blocks of 400ms overlapping by 75%, a shorter sound is a single block
+    let block = ((sample_rate as f64 * 0.4) as usize).clamp(1, powers.len());
+    let step = (block / 4).max(1);
+
+    let blocks: Vec<f64> = (0..=(powers.len() - block) / step)
+        .map(|index| powers[index * step..index * step + block].iter().sum::<f64>() / block as f64)
+        .filter(|mean_square| *mean_square > 0.0 && block_loudness(*mean_square) > -70.0)
+        .collect();
+
+    if blocks.is_empty() {
+        return None;
+    }
+
+    let relative_gate = block_loudness(blocks.iter().sum::<f64>() / blocks.len() as f64) - 10.0;
+    let gated: Vec<f64> = blocks
+        .into_iter()
+        .filter(|mean_square| block_loudness(*mean_square) > relative_gate)
+        .collect();
+
+    Some(block_loudness(gated.iter().sum::<f64>() / gated.len() as f64))
+}
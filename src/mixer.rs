@@ -0,0 +1,261 @@
+use crate::effects::{EnvironmentEffects, TrackEffects};
+use crate::error::error::OxAgAudioToolError;
+use crate::playback::LoadedSound;
+use crate::sound_config::OxAgSoundConfig;
+use crate::util::lock;
+use kira::clock::clock_info::ClockInfoProvider;
+use kira::clock::{ClockHandle, ClockTime};
+use kira::dsp::Frame;
+use kira::manager::backend::Backend;
+use kira::manager::AudioManager;
+use kira::modulator::value_provider::ModulatorValueProvider;
+use kira::track::effect::{Effect, EffectBuilder};
+use kira::track::{TrackBuilder, TrackHandle, TrackId, TrackRoutes};
+use kira::tween::Tween;
+use kira::OutputDestination;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// The category of a sound, each category plays on its own mixer track
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OxAgSoundCategory {
+    /// The [Event](robotics_lib::event::events::Event) sounds, the low energy warning and the sounds played with
+    /// [OxAgAudioTool::play_audio](crate::OxAgAudioTool::play_audio)
+    Event,
+    /// The [TileType](robotics_lib::world::tile::TileType) loops
+    Tile,
+    /// The [WeatherType](robotics_lib::world::environmental_conditions::WeatherType) loops
+    Weather,
+    /// The [Content](robotics_lib::world::tile::Content) loops
+    Content,
+    /// The adaptive music, the sequenced music and the playlist
+    Music,
+}
+
+impl OxAgSoundCategory {
+    /// All the categories
+    pub const ALL: [OxAgSoundCategory; 5] = [
+        OxAgSoundCategory::Event,
+        OxAgSoundCategory::Tile,
+        OxAgSoundCategory::Weather,
+        OxAgSoundCategory::Content,
+        OxAgSoundCategory::Music,
+    ];
+}
+
+/// Configures how the other categories get ducked while a sound that [ducks](OxAgSoundConfig::ducks) is playing
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OxAgDucking {
+    pub(crate) amplitude: f64,
+    pub(crate) attack: Duration,
+    pub(crate) release: Duration,
+}
+
+impl Default for OxAgDucking {
+    fn default() -> Self {
+        OxAgDucking {
+            amplitude: 0.3,
+            attack: Duration::from_millis(50),
+            release: Duration::from_millis(500),
+        }
+    }
+}
+
+impl OxAgDucking {
+    /// # Params
+    /// - amplitude - the volume of the ducked categories is multiplied by the provided amplitude
+    pub fn new(amplitude: f64) -> OxAgDucking {
+        OxAgDucking {
+            amplitude,
+            ..Default::default()
+        }
+    }
+
+    /// Sets how long the ducked categories take to dip
+    pub fn attack(&mut self, attack: Duration) {
+        self.attack = attack;
+    }
+
+    /// Sets how long the ducked categories take to restore once the sound is over
+    pub fn release(&mut self, release: Duration) {
+        self.release = release;
+    }
+}
+
+/// How a [Ducker] ducks its track, shared with the [Mixer]
+#[derive(Clone, Copy)]
+pub(crate) struct DuckState {
+    pub(crate) ducking: OxAgDucking,
+    /// The tick of the `timeline` clock until which the track stays ducked
+    pub(crate) until: Option<ClockTime>,
+}
+
+/// Ducks a category track until a tick of the `timeline` clock
+///
+/// The release happens on the audio thread, so the track comes back even if the tool doesn't get any event or update
+struct Ducker {
+    shared: Arc<Mutex<DuckState>>,
+    state: DuckState,
+    /// The amplitude the track is currently multiplied by
+    gain: f64,
+}
+
+impl Effect for Ducker {
+    fn on_start_processing(&mut self) {
+        // the audio thread never waits for the mixer, a state being changed gets picked up the next time
+        if let Ok(state) = self.shared.try_lock() {
+            self.state = *state;
+        }
+    }
+
+    fn process(
+        &mut self,
+        input: Frame,
+        dt: f64,
+        clock_info_provider: &ClockInfoProvider,
+        _modulator_value_provider: &ModulatorValueProvider,
+    ) -> Frame {
+        let ducking = self.state.ducking;
+        let ducked = self
+            .state
+            .until
+            .is_some_and(|until| clock_info_provider.get(until.clock).is_some_and(|clock| clock.ticks < until.ticks));
+
+        let (target, duration) = match ducked {
+            true => (ducking.amplitude, ducking.attack),
+            false => (1.0, ducking.release),
+        };
+
+        // the gain crosses the whole ducking range in the given duration
+        let range = (1.0 - ducking.amplitude).abs().max((self.gain - target).abs());
+        let step = match duration.is_zero() {
+            true => f64::INFINITY,
+            false => range * dt / duration.as_secs_f64(),
+        };
+
+        self.gain = match self.gain < target {
+            true => (self.gain + step).min(target),
+            false => (self.gain - step).max(target),
+        };
+
+        input * self.gain as f32
+    }
+}
+
+pub(crate) struct DuckerBuilder {
+    pub(crate) shared: Arc<Mutex<DuckState>>,
+}
+
+impl EffectBuilder for DuckerBuilder {
+    type Handle = ();
+
+    fn build(self) -> (Box<dyn Effect>, Self::Handle) {
+        let state = DuckState {
+            ducking: OxAgDucking::default(),
+            until: None,
+        };
+
+        (Box::new(Ducker { shared: self.shared, state, gain: 1.0 }), ())
+    }
+}
+
+/// The mixer track of a category together with its effects
+struct CategoryTrack {
+    handle: TrackHandle,
+    effects: TrackEffects,
+    /// The state of the [Ducker] of the track
+    ducking: Arc<Mutex<DuckState>>,
+}
+
+/// Owns the mixer track of each [OxAgSoundCategory], ducks them and applies the environment effects to them
+pub(crate) struct Mixer {
+    tracks: HashMap<OxAgSoundCategory, CategoryTrack>,
+    pub(crate) ducking: OxAgDucking,
+    /// The effect chains of the [TileType](robotics_lib::world::tile::TileType)s and [WeatherType](robotics_lib::world::environmental_conditions::WeatherType)s
+    pub(crate) environment: EnvironmentEffects,
+}
+
+impl Mixer {
+    /// Adds a sub track, with its effects, for each [OxAgSoundCategory], routed to the `output` track
+    pub(crate) fn new<B: Backend>(audio_manager: &mut AudioManager<B>, output: TrackId) -> Result<Mixer, OxAgAudioToolError> {
+        let mut tracks = HashMap::new();
+
+        for category in OxAgSoundCategory::ALL {
+            let ducking = Arc::new(Mutex::new(DuckState {
+                ducking: OxAgDucking::default(),
+                until: None,
+            }));
+
+            let mut builder = TrackBuilder::new().routes(TrackRoutes::parent(output));
+            builder.add_effect(DuckerBuilder { shared: ducking.clone() });
+            let effects = TrackEffects::add_to(&mut builder);
+
+            tracks.insert(category, CategoryTrack {
+                handle: audio_manager.add_sub_track(builder)?,
+                effects,
+                ducking,
+            });
+        }
+
+        Ok(Mixer {
+            tracks,
+            ducking: OxAgDucking::default(),
+            environment: EnvironmentEffects::default(),
+        })
+    }
+
+    /// Returns where the sounds of the category are played
+    pub(crate) fn destination(&self, category: OxAgSoundCategory) -> OutputDestination {
+        OutputDestination::Track(self.tracks[&category].handle.id())
+    }
+
+    /// Returns the config routed to the track of the category, unless it's routed somewhere else already
+    pub(crate) fn route(&self, category: OxAgSoundCategory, sound_config: &OxAgSoundConfig) -> OxAgSoundConfig {
+        sound_config.routed_to(self.destination(category))
+    }
+
+    /// Loads the sound routed to the track of the category, see [Mixer::route]
+    ///
+    /// The loaded sound keeps the configuration it was given, not the routed one
+    pub(crate) fn load(&self, category: OxAgSoundCategory, sound_config: &OxAgSoundConfig) -> Result<LoadedSound, OxAgAudioToolError> {
+        let mut sound = self.route(category, sound_config).to_loaded_sound()?;
+        sound.config = sound_config.clone();
+
+        Ok(sound)
+    }
+
+    /// Ducks all the categories but the given one for the given duration, measured on the `timeline` clock
+    ///
+    /// The categories get restored by the audio thread once the duration is over, a category ducked already stays ducked until the later end
+    pub(crate) fn duck(
+        &mut self,
+        category: OxAgSoundCategory,
+        timeline: &ClockHandle,
+        duration: Duration,
+    ) -> Result<(), OxAgAudioToolError> {
+        let until = timeline.time() + duration.as_millis() as u64;
+
+        for (ducked, track) in self.tracks.iter() {
+            if *ducked == category {
+                continue;
+            }
+
+            let mut state = lock(&track.ducking)?;
+            state.ducking = self.ducking;
+            state.until = match state.until {
+                Some(ducked_until) if ducked_until.clock == until.clock && ducked_until.ticks > until.ticks => Some(ducked_until),
+                _ => Some(until),
+            };
+        }
+
+        Ok(())
+    }
+
+    /// Tweens the effects of all the tracks to the active environment effect chains
+    pub(crate) fn update_effects(&mut self, tween: Tween) -> Result<(), OxAgAudioToolError> {
+        let tracks = self.tracks.iter_mut().map(|(category, track)| (category, &mut track.effects));
+
+        self.environment.apply(tracks, tween)
+    }
+}
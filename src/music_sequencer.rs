@@ -0,0 +1,386 @@
+use crate::error::error::OxAgAudioToolError;
+use crate::playback::{SoundHandle, SoundSource};
+use crate::sound_config::OxAgSoundConfig;
+use kira::clock::{ClockHandle, ClockSpeed, ClockTime};
+use kira::manager::backend::Backend;
+use kira::manager::AudioManager;
+use kira::tween::Tween;
+use kira::{OutputDestination, StartTime};
+use robotics_lib::event::events::Event;
+use robotics_lib::world::environmental_conditions::WeatherType;
+use robotics_lib::world::tile::TileType;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Where the sections of an [OxAgSequencedMusic] are allowed to change
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OxAgQuantization {
+    /// At the next beat
+    Beat,
+    /// At the next bar
+    #[default]
+    Bar,
+}
+
+/// A section of an [OxAgSequencedMusic], played while the robot is on one of its [TileType]s or while the weather is one of its [WeatherType]s
+#[derive(Debug)]
+pub struct OxAgMusicSection {
+    pub(crate) sound_config: OxAgSoundConfig,
+    pub(crate) tile_types: Vec<TileType>,
+    pub(crate) weather_types: Vec<WeatherType>,
+}
+
+impl OxAgMusicSection {
+    /// # Params
+    /// - sound_config - the [OxAgSoundConfig] of the section, it gets looped infinitely.
+    ///   Its length should be a whole number of bars
+    pub fn new(sound_config: OxAgSoundConfig) -> OxAgMusicSection {
+        let mut sound_config = sound_config;
+        sound_config.looped();
+
+        OxAgMusicSection {
+            sound_config,
+            tile_types: Vec::new(),
+            weather_types: Vec::new(),
+        }
+    }
+
+    /// The section plays while the robot is on the given [TileType]
+    pub fn for_tile_type(&mut self, tile_type: TileType) {
+        self.tile_types.push(tile_type);
+    }
+
+    /// The section plays while the weather is the given [WeatherType]
+    pub fn for_weather_type(&mut self, weather_type: WeatherType) {
+        self.weather_types.push(weather_type);
+    }
+}
+
+/// Configures a music track made of sections that follow the world, changing only on beat or bar boundaries
+///
+/// The section is chosen by looking first at the [TileType] the robot is on, then at the current [WeatherType],
+/// falling back to the default section. When more sections match, the one added first wins.
+#[derive(Debug)]
+pub struct OxAgSequencedMusic {
+    pub(crate) tempo: f64,
+    pub(crate) beats_per_bar: u64,
+    pub(crate) quantization: OxAgQuantization,
+    pub(crate) fade_out: Duration,
+    /// In the order they were added
+    pub(crate) sections: Vec<(String, OxAgMusicSection)>,
+    pub(crate) default_section: Option<String>,
+    pub(crate) transitions: HashMap<(String, String), (OxAgSoundConfig, u64)>,
+}
+
+impl OxAgSequencedMusic {
+    /// # Params
+    /// - tempo - the tempo of the music in beats per minute
+    /// - beats_per_bar - how many beats make up a bar
+    pub fn new(tempo: f64, beats_per_bar: u64) -> OxAgSequencedMusic {
+        OxAgSequencedMusic {
+            tempo,
+            beats_per_bar: beats_per_bar.max(1),
+            quantization: OxAgQuantization::default(),
+            fade_out: Duration::from_millis(100),
+            sections: Vec::new(),
+            default_section: None,
+            transitions: HashMap::new(),
+        }
+    }
+
+    /// Sets where the sections are allowed to change, see [OxAgQuantization]
+    pub fn quantization(&mut self, quantization: OxAgQuantization) {
+        self.quantization = quantization;
+    }
+
+    /// Sets how long the section being left takes to fade out once the boundary is reached
+    pub fn fade_out(&mut self, fade_out: Duration) {
+        self.fade_out = fade_out;
+    }
+
+    /// Adds a section with the given name, replacing the section with the same name if any
+    ///
+    /// The sections added first win over the later ones matching the same [TileType] or [WeatherType]
+    pub fn add_section(&mut self, name: &str, section: OxAgMusicSection) {
+        match self.sections.iter_mut().find(|(other, _)| other == name) {
+            Some((_, other)) => *other = section,
+            None => self.sections.push((name.to_string(), section)),
+        }
+    }
+
+    /// Sets the section played when no other section matches the state of the world
+    pub fn default_section(&mut self, name: &str) {
+        self.default_section = Some(name.to_string());
+    }
+
+    /// Adds a segment played between two sections
+    ///
+    /// # Params
+    /// - from - the name of the section being left
+    /// - to - the name of the section being entered
+    /// - sound_config - the [OxAgSoundConfig] of the segment
+    /// - beats - the length of the segment in beats, the new section starts once it's over
+    pub fn add_transition(&mut self, from: &str, to: &str, sound_config: OxAgSoundConfig, beats: u64) {
+        self.transitions.insert((from.to_string(), to.to_string()), (sound_config, beats));
+    }
+}
+
+/// A loaded section
+struct Section {
+    source: SoundSource,
+    tile_types: Vec<TileType>,
+    weather_types: Vec<WeatherType>,
+}
+
+/// A section that is playing or scheduled to play
+struct PlayingSection {
+    name: String,
+    handle: SoundHandle,
+    /// The tick of the boundary at which the section replaces the previous one
+    boundary: u64,
+}
+
+/// A playing [OxAgSequencedMusic]
+pub(crate) struct SequencedMusic {
+    /// Ticks once per beat
+    clock: ClockHandle,
+    /// The amount of beats between two points where the section can change
+    quantum: u64,
+    fade_out: Duration,
+    /// In the order they were added
+    sections: Vec<(String, Section)>,
+    default_section: Option<String>,
+    transitions: HashMap<(String, String), (SoundSource, u64)>,
+    /// The [TileType] the robot is on
+    tile_type: Option<TileType>,
+    /// The current [WeatherType]
+    weather_type: Option<WeatherType>,
+    /// The section playing (or scheduled to play)
+    current: Option<PlayingSection>,
+    /// The section that played before the current one, it keeps on playing until the boundary
+    previous: Option<PlayingSection>,
+    /// The transition segment playing (or scheduled to play)
+    transition: Option<SoundHandle>,
+}
+
+impl SequencedMusic {
+    /// Loads the music, played on the given destination, the clock starts together with the first section
+    ///
+    /// # Params
+    /// - tile_type - the [TileType] the robot is on, if known already
+    /// - weather_type - the current [WeatherType], if known already
+    pub(crate) fn new<B: Backend>(
+        audio_manager: &mut AudioManager<B>,
+        destination: OutputDestination,
+        music: &OxAgSequencedMusic,
+        tile_type: Option<TileType>,
+        weather_type: Option<WeatherType>,
+    ) -> Result<SequencedMusic, OxAgAudioToolError> {
+        let clock = audio_manager.add_clock(ClockSpeed::TicksPerMinute(music.tempo))?;
+
+        let mut sections = Vec::new();
+        for (name, section) in music.sections.iter() {
+            sections.push((name.clone(), Section {
+                source: section.sound_config.routed_to(destination).to_sound_source()?,
+                tile_types: section.tile_types.clone(),
+                weather_types: section.weather_types.clone(),
+            }));
+        }
+
+        let mut transitions = HashMap::new();
+        for (key, (sound_config, beats)) in music.transitions.iter() {
+            transitions.insert(key.clone(), (sound_config.routed_to(destination).to_sound_source()?, *beats));
+        }
+
+        Ok(SequencedMusic {
+            clock,
+            quantum: match music.quantization {
+                OxAgQuantization::Beat => 1,
+                OxAgQuantization::Bar => music.beats_per_bar,
+            },
+            fade_out: music.fade_out,
+            sections,
+            default_section: music.default_section.clone(),
+            transitions,
+            tile_type,
+            weather_type,
+            current: None,
+            previous: None,
+            transition: None,
+        })
+    }
+
+    /// Updates the state of the world the section depends on
+    pub(crate) fn observe(&mut self, event: &Event) {
+        match event {
+            Event::Moved(tile, _) => self.tile_type = Some(tile.tile_type),
+            Event::TimeChanged(environmental_conditions) | Event::DayChanged(environmental_conditions) => {
+                self.weather_type = Some(environmental_conditions.get_weather_condition());
+            }
+            _ => {}
+        }
+    }
+
+    /// Returns the section with the given name
+    fn section(&self, name: &str) -> Option<&Section> {
+        self.sections.iter().find(|(other, _)| other == name).map(|(_, section)| section)
+    }
+
+    /// Returns the name of the section matching the state of the world
+    fn target_section(&self) -> Option<String> {
+        let by_tile = self.tile_type.and_then(|tile_type| {
+            self.sections.iter().find(|(_, section)| section.tile_types.contains(&tile_type))
+        });
+        let by_weather = self.weather_type.as_ref().and_then(|weather_type| {
+            self.sections.iter().find(|(_, section)| section.weather_types.contains(weather_type))
+        });
+
+        by_tile
+            .or(by_weather)
+            .map(|(name, _)| name.clone())
+            .or_else(|| self.default_section.clone())
+    }
+
+    /// Schedules the change to the section matching the state of the world at the next beat or bar
+    pub(crate) fn update<B: Backend>(&mut self, audio_manager: &mut AudioManager<B>) -> Result<(), OxAgAudioToolError> {
+        let target = match self.target_section() {
+            Some(target) => target,
+            None => return Ok(()),
+        };
+
+        if self.current.as_ref().map(|current| &current.name) == Some(&target) {
+            return Ok(());
+        }
+
+        if self.section(&target).is_none() {
+            return Ok(());
+        }
+
+        let now = self.clock.time().ticks;
+
+        // a change that didn't happen yet is called off, the previous section keeps on playing as if it never was scheduled
+        if self.current.as_ref().is_some_and(|current| current.boundary > now) {
+            if let Some(mut previous) = self.previous.take() {
+                if let Some(mut current) = self.current.take() {
+                    let _ = current.handle.stop(Tween::default());
+                }
+
+                if let Some(mut transition) = self.transition.take() {
+                    let _ = transition.stop(Tween::default());
+                }
+
+                let _ = previous.handle.resume(Tween::default());
+                self.current = Some(previous);
+
+                if self.current.as_ref().map(|current| &current.name) == Some(&target) {
+                    return Ok(());
+                }
+            }
+        }
+
+        let section = match self.sections.iter().find(|(name, _)| *name == target) {
+            Some((_, section)) => section,
+            None => return Ok(()),
+        };
+        let clock = self.clock.id();
+
+        let boundary = match self.current.as_mut() {
+            // the first section starts together with the clock, so that the bars line up
+            None => {
+                let handle = section.source.play(audio_manager, |settings| {
+                    settings.start_time(ClockTime { clock, ticks: 0 })
+                })?;
+                self.clock.start()?;
+                self.current = Some(PlayingSection { name: target, handle, boundary: 0 });
+                return Ok(());
+            }
+            Some(current) => {
+                let boundary = ClockTime {
+                    clock,
+                    ticks: (now / self.quantum + 1) * self.quantum,
+                };
+
+                let _ = current.handle.stop(Tween {
+                    start_time: StartTime::ClockTime(boundary),
+                    duration: self.fade_out,
+                    ..Default::default()
+                });
+
+                boundary
+            }
+        };
+
+        if let Some(mut transition) = self.transition.take() {
+            let _ = transition.stop(Tween::default());
+        }
+
+        self.previous = self.current.take();
+        let previous = self.previous.as_ref().map(|previous| previous.name.clone()).unwrap_or_default();
+        let mut section_start = boundary;
+
+        if let Some((source, beats)) = self.transitions.get(&(previous, target.clone())) {
+            self.transition = Some(source.play(audio_manager, |settings| settings.start_time(boundary))?);
+            section_start = boundary + *beats;
+        }
+
+        let handle = section.source.play(audio_manager, |settings| settings.start_time(section_start))?;
+        self.current = Some(PlayingSection { name: target, handle, boundary: boundary.ticks });
+
+        Ok(())
+    }
+
+    /// Fades out and stops the music
+    pub(crate) fn stop(&mut self, tween: Tween) {
+        for mut section in self.current.take().into_iter().chain(self.previous.take()) {
+            let _ = section.handle.stop(tween);
+        }
+
+        if let Some(mut transition) = self.transition.take() {
+            let _ = transition.stop(tween);
+        }
+    }
+
+    /// Fades out and pauses the sections and the transition, the clock is paused too so that the boundaries wait for them
+    ///
+    /// Pausing calls off the fade out of the previous section, [SequencedMusic::resume] schedules it again
+    pub(crate) fn pause(&mut self, tween: Tween) -> Result<(), OxAgAudioToolError> {
+        for section in self.current.iter_mut().chain(self.previous.iter_mut()) {
+            let _ = section.handle.pause(tween);
+        }
+
+        if let Some(transition) = self.transition.as_mut() {
+            let _ = transition.pause(tween);
+        }
+
+        Ok(self.clock.pause()?)
+    }
+
+    /// Fades the music paused by [SequencedMusic::pause] back in
+    pub(crate) fn resume(&mut self, tween: Tween) -> Result<(), OxAgAudioToolError> {
+        // the clock only runs once the first section got played
+        if self.current.is_some() {
+            self.clock.start()?;
+        }
+
+        for section in self.current.iter_mut().chain(self.previous.iter_mut()) {
+            let _ = section.handle.unpause(tween);
+        }
+
+        if let Some(transition) = self.transition.as_mut() {
+            let _ = transition.unpause(tween);
+        }
+
+        if let (Some(current), Some(previous)) = (self.current.as_ref(), self.previous.as_mut()) {
+            let _ = previous.handle.stop(Tween {
+                start_time: StartTime::ClockTime(ClockTime {
+                    clock: self.clock.id(),
+                    ticks: current.boundary,
+                }),
+                duration: self.fade_out,
+                ..Default::default()
+            });
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,378 @@
+use crate::amount_scaling::OxAgAmountScaling;
+use crate::loudness::OxAgLoudnessTarget;
+use crate::error::error::OxAgAudioToolError;
+use crate::sound_config::{OxAgLoopStart, OxAgSoundConfig};
+use crate::validation::OxAgMappingKey;
+use kira::manager::backend::Backend;
+use kira::manager::AudioManager;
+use kira::sound::static_sound::{StaticSoundData, StaticSoundHandle, StaticSoundSettings};
+use kira::sound::streaming::StreamingSoundHandle;
+use kira::sound::{FromFileError, PlaybackRate, PlaybackState};
+use kira::tween::{Tween, Value};
+use kira::{CommandError, Volume};
+use rand::Rng;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// A sound ready to be played, either decoded in memory or streamed from its file
+pub(crate) enum SoundSource {
+    /// The fully decoded sound
+    Static(StaticSoundData),
+    /// The configuration the sound gets streamed from every time it's played, together with its duration
+    Streaming(OxAgSoundConfig, Duration),
+}
+
+impl SoundSource {
+    /// Returns the settings the sound is played with
+    pub(crate) fn settings(&self) -> StaticSoundSettings {
+        match self {
+            SoundSource::Static(data) => data.settings,
+            SoundSource::Streaming(sound_config, _) => sound_config.settings,
+        }
+    }
+
+    /// Returns the duration of the audio
+    pub(crate) fn duration(&self) -> Duration {
+        match self {
+            SoundSource::Static(data) => data.duration(),
+            SoundSource::Streaming(_, duration) => *duration,
+        }
+    }
+
+    /// Sets the volume the sound is played with
+    pub(crate) fn set_volume(&mut self, volume: Value<Volume>) {
+        match self {
+            SoundSource::Static(data) => data.settings.volume = volume,
+            SoundSource::Streaming(sound_config, _) => sound_config.settings.volume = volume,
+        }
+    }
+
+    /// Plays the sound with its settings modified by `modify`
+    pub(crate) fn play<B: Backend>(
+        &self,
+        audio_manager: &mut AudioManager<B>,
+        modify: impl FnOnce(StaticSoundSettings) -> StaticSoundSettings,
+    ) -> Result<SoundHandle, OxAgAudioToolError> {
+        match self {
+            SoundSource::Static(data) => {
+                let data = data.with_modified_settings(modify);
+                Ok(SoundHandle::Static(audio_manager.play(data)?))
+            }
+            SoundSource::Streaming(sound_config, _) => {
+                let mut sound_config = sound_config.clone();
+                sound_config.settings = modify(sound_config.settings);
+                Ok(SoundHandle::Streaming(audio_manager.play(sound_config.to_streaming_sound_data()?)?))
+            }
+        }
+    }
+}
+
+/// The handle of a playing [SoundSource]
+pub(crate) enum SoundHandle {
+    Static(StaticSoundHandle),
+    Streaming(StreamingSoundHandle<FromFileError>),
+}
+
+impl SoundHandle {
+    pub(crate) fn set_volume(&mut self, volume: impl Into<Value<Volume>>, tween: Tween) -> Result<(), CommandError> {
+        match self {
+            SoundHandle::Static(handle) => handle.set_volume(volume, tween),
+            SoundHandle::Streaming(handle) => handle.set_volume(volume, tween),
+        }
+    }
+
+    pub(crate) fn set_playback_rate(&mut self, playback_rate: impl Into<Value<PlaybackRate>>, tween: Tween) -> Result<(), CommandError> {
+        match self {
+            SoundHandle::Static(handle) => handle.set_playback_rate(playback_rate, tween),
+            SoundHandle::Streaming(handle) => handle.set_playback_rate(playback_rate, tween),
+        }
+    }
+
+    pub(crate) fn set_panning(&mut self, panning: impl Into<Value<f64>>, tween: Tween) -> Result<(), CommandError> {
+        match self {
+            SoundHandle::Static(handle) => handle.set_panning(panning, tween),
+            SoundHandle::Streaming(handle) => handle.set_panning(panning, tween),
+        }
+    }
+
+    /// Returns whether the sound is playing, paused or stopped
+    pub(crate) fn state(&self) -> PlaybackState {
+        match self {
+            SoundHandle::Static(handle) => handle.state(),
+            SoundHandle::Streaming(handle) => handle.state(),
+        }
+    }
+
+    /// Returns the playback position in seconds
+    pub(crate) fn position(&self) -> f64 {
+        match self {
+            SoundHandle::Static(handle) => handle.position(),
+            SoundHandle::Streaming(handle) => handle.position(),
+        }
+    }
+
+    /// Pauses the sound, it also calls off a stop whose fade didn't start yet
+    pub(crate) fn pause(&mut self, tween: Tween) -> Result<(), CommandError> {
+        match self {
+            SoundHandle::Static(handle) => handle.pause(tween),
+            SoundHandle::Streaming(handle) => handle.pause(tween),
+        }
+    }
+
+    /// Resumes the sound if it got paused by [SoundHandle::pause]
+    pub(crate) fn unpause(&mut self, tween: Tween) -> Result<(), CommandError> {
+        match self.state() {
+            PlaybackState::Pausing | PlaybackState::Paused => self.resume(tween),
+            _ => Ok(()),
+        }
+    }
+
+    /// Resumes a paused sound, it also calls off a stop whose fade didn't start yet
+    pub(crate) fn resume(&mut self, tween: Tween) -> Result<(), CommandError> {
+        match self {
+            SoundHandle::Static(handle) => handle.resume(tween),
+            SoundHandle::Streaming(handle) => handle.resume(tween),
+        }
+    }
+
+    pub(crate) fn stop(&mut self, tween: Tween) -> Result<(), CommandError> {
+        match self {
+            SoundHandle::Static(handle) => handle.stop(tween),
+            SoundHandle::Streaming(handle) => handle.stop(tween),
+        }
+    }
+}
+
+/// A one-shot that may still be playing
+pub(crate) struct PlayingOneShot {
+    /// What the sound is mapped to, if anything
+    pub(crate) key: Option<OxAgMappingKey>,
+    /// The handle of the playing sound
+    pub(crate) handle: SoundHandle,
+}
+
+/// Keeps the handle of a playing one-shot, forgetting the ones that are over
+pub(crate) fn track_one_shot(one_shots: &mut Vec<PlayingOneShot>, key: Option<OxAgMappingKey>, handle: SoundHandle) {
+    one_shots.retain(|one_shot| one_shot.handle.state() != PlaybackState::Stopped);
+    one_shots.push(PlayingOneShot { key, handle });
+}
+
+/// A sound together with the asset it was loaded from
+pub(crate) struct LoadedSound {
+    /// The configuration the sound was loaded from
+    pub(crate) config: OxAgSoundConfig,
+    /// Identifies the audio file, two sounds with the same asset are the same loop
+    pub(crate) asset: String,
+    /// The decoded or streamed sound
+    pub(crate) source: SoundSource,
+    /// Where the sound starts when it's played as a loop
+    pub(crate) loop_start: OxAgLoopStart,
+    /// How the sound is scaled by the amount carried by an event
+    pub(crate) amount_scaling: OxAgAmountScaling,
+    /// Whether the other categories get ducked while the sound plays
+    pub(crate) ducks: bool,
+    /// The configured volume, before the loudness normalization
+    pub(crate) volume: Value<Volume>,
+    /// The loudness the sound gets normalized to
+    pub(crate) loudness_target: OxAgLoudnessTarget,
+}
+
+/// A looping sound that is currently being played for a given key (e.g. a [robotics_lib::world::tile::TileType])
+pub(crate) struct PlayingLoop<K> {
+    /// The key the loop is currently playing for
+    pub(crate) key: K,
+    /// The asset of the playing sound
+    pub(crate) asset: String,
+    /// The handle of the playing sound
+    pub(crate) handle: SoundHandle,
+    /// When the sound started playing, in tool time
+    pub(crate) started_at: Duration,
+}
+
+/// Decides when a loop is allowed to switch to a new key, so that walking along a border doesn't swap loops on every step
+pub(crate) struct Debounce<K> {
+    /// The amount of consecutive steps on a new key before switching
+    pub(crate) min_steps: usize,
+    /// The minimum amount of time spent on a new key before switching
+    pub(crate) min_time: Duration,
+    /// The key waiting to be switched to, how many consecutive steps it was seen for and since when, in tool time
+    pending: Option<(K, usize, Duration)>,
+}
+
+impl<K> Default for Debounce<K> {
+    fn default() -> Self {
+        Debounce {
+            min_steps: 1,
+            min_time: Duration::ZERO,
+            pending: None,
+        }
+    }
+}
+
+impl<K: PartialEq> Debounce<K> {
+    /// Registers a step on `key` at the tool time `now` while the loop is playing for `current`
+    ///
+    /// Returns whether the loop can switch to `key`
+    pub(crate) fn settle(&mut self, key: K, current: Option<&K>, now: Duration) -> bool {
+        if current == Some(&key) {
+            self.pending = None;
+            return true;
+        }
+
+        match self.pending.as_mut() {
+            Some((pending, steps, _)) if *pending == key => *steps += 1,
+            _ => self.pending = Some((key, 1, now)),
+        }
+
+        let (_, steps, since) = self.pending.as_ref().unwrap();
+        *steps >= self.min_steps && now - *since >= self.min_time
+    }
+
+    /// Forgets the key waiting to be switched to, its steps start over
+    pub(crate) fn reset(&mut self) {
+        self.pending = None;
+    }
+
+    /// Returns whether the loop playing for `current` can switch to `key` at the tool time `now`, without registering a step
+    pub(crate) fn is_settled(&self, key: &K, current: Option<&K>, now: Duration) -> bool {
+        if current == Some(key) {
+            return true;
+        }
+
+        match self.pending.as_ref() {
+            Some((pending, steps, since)) => pending == key && *steps >= self.min_steps && now - *since >= self.min_time,
+            None => false,
+        }
+    }
+}
+
+/// Handles switching between loops, remembering where each asset was stopped
+#[derive(Default)]
+pub(crate) struct LoopSwitcher {
+    /// The tween used to adjust a loop to the settings of a new key sharing the same asset
+    pub(crate) shared_asset_tween: Option<Tween>,
+    /// The minimum amount of time a loop plays before it can be replaced
+    pub(crate) min_dwell: Duration,
+    /// How long a loop takes to fade out while the one replacing it fades in
+    pub(crate) crossfade: Duration,
+    /// The position in seconds at which each asset was last stopped
+    positions: HashMap<String, f64>,
+}
+
+impl LoopSwitcher {
+    /// Switches the loop in `current` so that it plays the sound configured for `key`
+    ///
+    /// If the sound for `key` uses the same asset as the loop currently playing, the loop keeps on playing.
+    /// When a shared asset tween is set, the volume, playback rate and panning of the loop get tweened to the settings of the new sound.
+    ///
+    /// A loop that has been playing for less than the minimum dwell time, at the tool time `now`, is not replaced.
+    ///
+    /// Returns whether `current` is now playing for `key`, `false` if the minimum dwell time held the switch back
+    pub(crate) fn switch<K: PartialEq, B: Backend>(
+        &mut self,
+        audio_manager: &mut AudioManager<B>,
+        current: &mut Option<PlayingLoop<K>>,
+        key: K,
+        sound: Option<&LoadedSound>,
+        rng: &mut impl Rng,
+        now: Duration,
+    ) -> Result<bool, OxAgAudioToolError> {
+        if let Some(playing) = current.as_ref() {
+            if playing.key == key {
+                return Ok(true);
+            }
+        }
+
+        if let (Some(playing), Some(sound)) = (current.as_mut(), sound) {
+            if playing.asset == sound.asset {
+                playing.key = key;
+
+                if let Some(tween) = self.shared_asset_tween {
+                    let settings = sound.source.settings();
+                    let _ = playing.handle.set_volume(settings.volume, tween);
+                    let _ = playing.handle.set_playback_rate(settings.playback_rate, tween);
+                    let _ = playing.handle.set_panning(settings.panning, tween);
+                }
+
+                return Ok(true);
+            }
+        }
+
+        if let Some(playing) = current.as_ref() {
+            if now - playing.started_at < self.min_dwell {
+                return Ok(false);
+            }
+        }
+
+        if let Some(previous) = current.take() {
+            self.stop(previous);
+        }
+
+        if let Some(sound) = sound {
+            *current = Some(self.start(audio_manager, key, sound, rng, now)?);
+        }
+
+        Ok(true)
+    }
+
+    /// Starts playing `sound` as the loop for `key` at the tool time `now`
+    pub(crate) fn start<K, B: Backend>(
+        &self,
+        audio_manager: &mut AudioManager<B>,
+        key: K,
+        sound: &LoadedSound,
+        rng: &mut impl Rng,
+        now: Duration,
+    ) -> Result<PlayingLoop<K>, OxAgAudioToolError> {
+        Ok(PlayingLoop {
+            key,
+            asset: sound.asset.clone(),
+            handle: sound.source.play(audio_manager, |settings| self.start_settings(sound, settings, rng))?,
+            started_at: now,
+        })
+    }
+
+    /// Stops a loop, remembering its position
+    pub(crate) fn stop<K>(&mut self, playing: PlayingLoop<K>) {
+        let tween = match self.crossfade.is_zero() {
+            true => Tween::default(),
+            false => Tween {
+                duration: self.crossfade,
+                ..Default::default()
+            },
+        };
+
+        self.stop_with(playing, tween);
+    }
+
+    /// Fades out and stops a loop with the given tween instead of the crossfade, remembering its position
+    pub(crate) fn stop_with<K>(&mut self, mut playing: PlayingLoop<K>, tween: Tween) {
+        self.positions.insert(playing.asset, playing.handle.position());
+        let _ = playing.handle.stop(tween);
+    }
+
+    /// Returns the settings of the sound starting at the position dictated by its [OxAgLoopStart]
+    pub(crate) fn start_settings(&self, sound: &LoadedSound, settings: StaticSoundSettings, rng: &mut impl Rng) -> StaticSoundSettings {
+        let duration = sound.source.duration().as_secs_f64();
+        let settings = match self.crossfade.is_zero() {
+            true => settings,
+            false => settings.fade_in_tween(Tween {
+                duration: self.crossfade,
+                ..Default::default()
+            }),
+        };
+
+        let offset = match sound.loop_start {
+            OxAgLoopStart::Beginning => 0.0,
+            OxAgLoopStart::Resume => self.positions.get(&sound.asset).copied().unwrap_or(0.0),
+            OxAgLoopStart::Random if duration > 0.0 => rng.gen_range(0.0..duration),
+            OxAgLoopStart::Random => 0.0,
+        };
+
+        if offset <= 0.0 || offset >= duration {
+            return settings;
+        }
+
+        settings.playback_region(offset..)
+    }
+}
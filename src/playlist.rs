@@ -0,0 +1,418 @@
+use crate::error::error::OxAgAudioToolError;
+use crate::sound_config::OxAgSoundConfig;
+use kira::clock::{ClockHandle, ClockTime};
+use kira::manager::backend::Backend;
+use kira::manager::AudioManager;
+use kira::sound::streaming::StreamingSoundHandle;
+use kira::sound::{FromFileError, PlaybackState};
+use kira::tween::{Tween, Value};
+use kira::{OutputDestination, StartTime};
+use rand::seq::SliceRandom;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// The order in which the tracks of an [OxAgPlaylist] are played
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OxAgPlaylistOrder {
+    /// In the order they were added
+    #[default]
+    Ordered,
+    /// In a random order, reshuffled every time the playlist repeats
+    Shuffled,
+}
+
+/// What happens when a track of an [OxAgPlaylist] ends
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OxAgRepeatMode {
+    /// The next track plays, the playlist stops after the last one
+    #[default]
+    Off,
+    /// The same track plays again
+    One,
+    /// The next track plays, the playlist starts over after the last one
+    All,
+}
+
+/// How a track of an [OxAgPlaylist] leads into the next one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OxAgPlaylistTransition {
+    /// The next track starts on the sample the current one ends
+    #[default]
+    Gapless,
+    /// The next track fades in while the current one fades out, over the given duration
+    Crossfade(Duration),
+}
+
+/// Something that happened to the playing [OxAgPlaylist], see [OxAgAudioTool::pop_playlist_event](crate::OxAgAudioTool::pop_playlist_event)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OxAgPlaylistEvent {
+    /// A track started playing
+    /// # Properties
+    /// - track - the index of the track, in the order it was added to the playlist
+    /// - path - the path to the audio file of the track
+    TrackChanged { track: usize, path: String },
+    /// The last track ended and the playlist doesn't repeat
+    Finished,
+}
+
+/// Configures a playlist of background music tracks
+///
+/// The tracks are streamed from their files while playing, so long tracks don't need to be fully loaded in memory.
+#[derive(Debug, Default)]
+pub struct OxAgPlaylist {
+    pub(crate) tracks: Vec<OxAgSoundConfig>,
+    pub(crate) order: OxAgPlaylistOrder,
+    pub(crate) repeat: OxAgRepeatMode,
+    pub(crate) transition: OxAgPlaylistTransition,
+}
+
+impl OxAgPlaylist {
+    /// An empty playlist, played in order, gapless and without repeating
+    pub fn new() -> OxAgPlaylist {
+        OxAgPlaylist::default()
+    }
+
+    /// Adds a track to the playlist
+    ///
+    /// # Side effects
+    /// The track is never looped, the [OxAgRepeatMode] decides whether it plays again
+    pub fn add_track(&mut self, sound_config: OxAgSoundConfig) {
+        let mut sound_config = sound_config;
+        sound_config.settings.loop_region = None;
+
+        self.tracks.push(sound_config);
+    }
+
+    /// Sets the order of the tracks, see [OxAgPlaylistOrder]
+    pub fn order(&mut self, order: OxAgPlaylistOrder) {
+        self.order = order;
+    }
+
+    /// Sets what happens when a track ends, see [OxAgRepeatMode]
+    pub fn repeat(&mut self, repeat: OxAgRepeatMode) {
+        self.repeat = repeat;
+    }
+
+    /// Sets how a track leads into the next one, see [OxAgPlaylistTransition]
+    pub fn transition(&mut self, transition: OxAgPlaylistTransition) {
+        self.transition = transition;
+    }
+}
+
+/// A track playing (or scheduled to play) on the `timeline` clock
+struct ScheduledTrack {
+    /// The position of the track in the play order
+    position: usize,
+    /// The index of the track, in the order it was added to the playlist
+    track: usize,
+    handle: StreamingSoundHandle<FromFileError>,
+    /// The tick the track starts at
+    start: u64,
+    /// The tick the track ends at
+    end: u64,
+    /// The reshuffled play order the track starts, it replaces the current one once the track becomes current
+    new_order: Option<Vec<usize>>,
+}
+
+/// A playing [OxAgPlaylist]
+pub(crate) struct Playlist {
+    tracks: Vec<OxAgSoundConfig>,
+    shuffled: bool,
+    repeat: OxAgRepeatMode,
+    crossfade: Duration,
+    /// The indexes of the tracks in the order they get played
+    order: Vec<usize>,
+    current: Option<ScheduledTrack>,
+    /// The track scheduled to follow the current one
+    next: Option<ScheduledTrack>,
+    events: VecDeque<OxAgPlaylistEvent>,
+}
+
+impl Playlist {
+    /// Starts the first track of the playlist right away, played on the given destination
+    pub(crate) fn play<B: Backend>(
+        audio_manager: &mut AudioManager<B>,
+        timeline: &ClockHandle,
+        destination: OutputDestination,
+        playlist: &OxAgPlaylist,
+    ) -> Result<Playlist, OxAgAudioToolError> {
+        let mut order: Vec<usize> = (0..playlist.tracks.len()).collect();
+        let shuffled = playlist.order == OxAgPlaylistOrder::Shuffled;
+
+        if shuffled {
+            order.shuffle(&mut rand::thread_rng());
+        }
+
+        let mut playlist = Playlist {
+            tracks: playlist.tracks.iter().map(|track| track.routed_to(destination)).collect(),
+            shuffled,
+            repeat: playlist.repeat,
+            crossfade: match playlist.transition {
+                OxAgPlaylistTransition::Gapless => Duration::ZERO,
+                OxAgPlaylistTransition::Crossfade(duration) => duration,
+            },
+            order,
+            current: None,
+            next: None,
+            events: VecDeque::new(),
+        };
+
+        playlist.jump(audio_manager, timeline, Some((0, None)))?;
+
+        Ok(playlist)
+    }
+
+    /// Returns the position following the given one in the play order
+    ///
+    /// When a shuffled playlist starts over, the reshuffled order the position refers to is returned too.
+    /// It only replaces the current order once its first track becomes current, so that the previous track is still the one that was played
+    ///
+    /// # Params
+    /// - skipping - whether the user is skipping the track, in which case [OxAgRepeatMode::One] is ignored
+    pub(crate) fn next_position(&self, position: usize, skipping: bool) -> Option<(usize, Option<Vec<usize>>)> {
+        if self.repeat == OxAgRepeatMode::One && !skipping {
+            return Some((position, None));
+        }
+
+        if position + 1 < self.order.len() {
+            return Some((position + 1, None));
+        }
+
+        if self.repeat == OxAgRepeatMode::Off || self.order.is_empty() {
+            return None;
+        }
+
+        if !self.shuffled {
+            return Some((0, None));
+        }
+
+        let mut order = self.order.clone();
+        order.shuffle(&mut rand::thread_rng());
+
+        Some((0, Some(order)))
+    }
+
+    /// Starts the track at the given position of the play order
+    ///
+    /// # Params
+    /// - new_order - the reshuffled play order the position refers to, [None] meaning the current one
+    /// - start - the tick of the `timeline` clock the track starts at, [None] or a tick in the past meaning right away
+    /// - fade_in - the duration of the fade in
+    fn start_track<B: Backend>(
+        &self,
+        audio_manager: &mut AudioManager<B>,
+        timeline: &ClockHandle,
+        (position, new_order): (usize, Option<Vec<usize>>),
+        start: Option<u64>,
+        fade_in: Duration,
+    ) -> Result<ScheduledTrack, OxAgAudioToolError> {
+        let track = new_order.as_deref().unwrap_or(&self.order)[position];
+        let mut data = self.tracks[track].to_streaming_sound_data()?;
+
+        let playback_rate = match data.settings.playback_rate {
+            Value::Fixed(playback_rate) => playback_rate.as_factor(),
+            _ => 1.0,
+        };
+        let length = (data.duration().as_secs_f64() * 1000.0 / playback_rate) as u64;
+
+        let now = timeline.time().ticks;
+        let start = start.unwrap_or(now).max(now);
+
+        data.settings.start_time = match start > now {
+            true => StartTime::ClockTime(ClockTime { clock: timeline.id(), ticks: start }),
+            false => StartTime::Immediate,
+        };
+
+        if !fade_in.is_zero() {
+            data.settings.fade_in_tween = Some(Tween {
+                duration: fade_in,
+                ..Default::default()
+            });
+        }
+
+        Ok(ScheduledTrack {
+            position,
+            track,
+            handle: audio_manager.play(data)?,
+            start,
+            end: start + length,
+            new_order,
+        })
+    }
+
+    /// Schedules the track following the current one, so that it starts exactly when the current one ends
+    /// (or fades out, when crossfading)
+    fn schedule_next<B: Backend>(&mut self, audio_manager: &mut AudioManager<B>, timeline: &ClockHandle) -> Result<(), OxAgAudioToolError> {
+        if self.next.is_some() {
+            return Ok(());
+        }
+
+        let (position, start, end) = match self.current.as_ref() {
+            Some(current) => (current.position, current.start, current.end),
+            None => return Ok(()),
+        };
+
+        let next_position = match self.next_position(position, false) {
+            Some(next_position) => next_position,
+            None => return Ok(()),
+        };
+
+        let crossfade_start = end.saturating_sub(self.crossfade.as_millis() as u64).max(start);
+        self.next = Some(self.start_track(audio_manager, timeline, next_position, Some(crossfade_start), self.crossfade)?);
+        self.schedule_crossfade(timeline);
+
+        Ok(())
+    }
+
+    /// Schedules the fade out of the current track, so that it crossfades with the next one
+    fn schedule_crossfade(&mut self, timeline: &ClockHandle) {
+        if self.crossfade.is_zero() {
+            return;
+        }
+
+        if let (Some(current), Some(next)) = (self.current.as_mut(), self.next.as_ref()) {
+            let _ = current.handle.stop(Tween {
+                start_time: StartTime::ClockTime(ClockTime { clock: timeline.id(), ticks: next.start }),
+                duration: self.crossfade,
+                ..Default::default()
+            });
+        }
+    }
+
+    /// Stops the playing tracks and starts the one at the given position (and reshuffled order, if any) right away, [None] ending the playlist
+    fn jump<B: Backend>(
+        &mut self,
+        audio_manager: &mut AudioManager<B>,
+        timeline: &ClockHandle,
+        position: Option<(usize, Option<Vec<usize>>)>,
+    ) -> Result<(), OxAgAudioToolError> {
+        let fade_out = Tween {
+            duration: self.crossfade,
+            ..Default::default()
+        };
+        self.stop(fade_out);
+
+        let position = match position {
+            Some(position) if position.0 < self.order.len() => position,
+            _ => {
+                self.events.push_back(OxAgPlaylistEvent::Finished);
+                return Ok(());
+            }
+        };
+
+        let mut current = self.start_track(audio_manager, timeline, position, None, self.crossfade)?;
+        if let Some(order) = current.new_order.take() {
+            self.order = order;
+        }
+
+        self.push_track_changed(current.track);
+        self.current = Some(current);
+        self.schedule_next(audio_manager, timeline)
+    }
+
+    fn push_track_changed(&mut self, track: usize) {
+        self.events.push_back(OxAgPlaylistEvent::TrackChanged {
+            track,
+            path: self.tracks[track].path.clone(),
+        });
+    }
+
+    /// Moves on to the scheduled track once it started and schedules the following one
+    ///
+    /// After a late update the tracks that started in the meantime become current one after the other,
+    /// the ones that should have started while nothing was scheduled start right away
+    pub(crate) fn update<B: Backend>(&mut self, audio_manager: &mut AudioManager<B>, timeline: &ClockHandle) -> Result<(), OxAgAudioToolError> {
+        let now = timeline.time().ticks;
+
+        while let Some(mut next) = self.next.take() {
+            if now < next.start {
+                self.next = Some(next);
+                return Ok(());
+            }
+
+            // a track that starts right now is as far as the playlist can catch up
+            let caught_up = next.start >= now;
+
+            if let Some(order) = next.new_order.take() {
+                self.order = order;
+            }
+
+            self.push_track_changed(next.track);
+            self.current = Some(next);
+            self.schedule_next(audio_manager, timeline)?;
+
+            if caught_up {
+                return Ok(());
+            }
+        }
+
+        if self.current.as_ref().is_some_and(|current| now >= current.end) {
+            self.current = None;
+            self.events.push_back(OxAgPlaylistEvent::Finished);
+        }
+
+        Ok(())
+    }
+
+    /// Starts the next track right away
+    pub(crate) fn skip<B: Backend>(&mut self, audio_manager: &mut AudioManager<B>, timeline: &ClockHandle) -> Result<(), OxAgAudioToolError> {
+        let position = match self.current.as_ref() {
+            Some(current) => self.next_position(current.position, true),
+            None => return Ok(()),
+        };
+
+        self.jump(audio_manager, timeline, position)
+    }
+
+    /// Starts the previous track right away, the first track restarts unless the whole playlist repeats
+    pub(crate) fn previous<B: Backend>(&mut self, audio_manager: &mut AudioManager<B>, timeline: &ClockHandle) -> Result<(), OxAgAudioToolError> {
+        let position = match self.current.as_ref() {
+            Some(current) => current.position,
+            None => return Ok(()),
+        };
+
+        let position = match position.checked_sub(1) {
+            Some(position) => position,
+            None if self.repeat == OxAgRepeatMode::All => self.order.len() - 1,
+            None => 0,
+        };
+
+        self.jump(audio_manager, timeline, Some((position, None)))
+    }
+
+    /// Returns the oldest event that wasn't returned yet
+    pub(crate) fn pop_event(&mut self) -> Option<OxAgPlaylistEvent> {
+        self.events.pop_front()
+    }
+
+    /// Fades out and stops the playing and the scheduled tracks
+    pub(crate) fn stop(&mut self, tween: Tween) {
+        if let Some(mut current) = self.current.take() {
+            let _ = current.handle.stop(tween);
+        }
+
+        if let Some(mut next) = self.next.take() {
+            let _ = next.handle.stop(Tween::default());
+        }
+    }
+
+    /// Fades out and pauses the current and the next track
+    ///
+    /// Pausing calls off the crossfade of the current track, [Playlist::resume] schedules it again
+    pub(crate) fn pause(&mut self, tween: Tween) {
+        for scheduled in self.current.iter_mut().chain(self.next.iter_mut()) {
+            let _ = scheduled.handle.pause(tween);
+        }
+    }
+
+    /// Fades the tracks paused by [Playlist::pause] back in
+    pub(crate) fn resume(&mut self, timeline: &ClockHandle, tween: Tween) {
+        for scheduled in self.current.iter_mut().chain(self.next.iter_mut()) {
+            if matches!(scheduled.handle.state(), PlaybackState::Pausing | PlaybackState::Paused) {
+                let _ = scheduled.handle.resume(tween);
+            }
+        }
+
+        self.schedule_crossfade(timeline);
+    }
+}
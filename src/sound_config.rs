@@ -1,34 +1,83 @@
+use crate::amount_scaling::OxAgAmountScaling;
 use crate::error::error::OxAgAudioToolError;
+use crate::loudness::OxAgLoudnessTarget;
+use crate::playback::{LoadedSound, SoundSource};
 use kira::sound::static_sound::{StaticSoundData, StaticSoundSettings};
+use kira::sound::streaming::{StreamingSoundData, StreamingSoundSettings};
+use kira::sound::FromFileError;
 use kira::sound::{EndPosition, PlaybackPosition, Region};
 use kira::tween::Value;
-use kira::Volume;
+use kira::{OutputDestination, Volume};
+use std::fs;
+
+/// Where a looped [TileType](robotics_lib::world::tile::TileType) or [WeatherType](robotics_lib::world::environmental_conditions::WeatherType)
+/// sound starts playing every time it gets (re)started
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OxAgLoopStart {
+    /// The loop always starts from the beginning of the audio file
+    #[default]
+    Beginning,
+    /// The loop resumes from the position it was at when it was last stopped
+    Resume,
+    /// The loop starts from a random position of the audio file
+    Random,
+}
+
+/// Whether a sound gets fully decoded in memory when it's loaded or streamed from its file while it's playing
+///
+/// Streaming keeps the memory usage and the startup time low for long files (e.g. music or ambience loops),
+/// at the cost of reading the file while the sound is playing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OxAgStreaming {
+    /// The sound is always decoded in memory
+    #[default]
+    Never,
+    /// The sound is always streamed
+    Always,
+    /// The sound is streamed if its file is bigger than the given amount of bytes
+    AboveSize(u64),
+}
 
 /// Configures any sound that should be played via this library
 /// # Properties
 /// - path - the path to the audio file, supported formats are: **mp3**, **ogg**, **wav**, **flac**
 /// - settings - the actual [StaticSoundSettings]
-#[derive(Debug)]
+/// - loop_start - where the sound starts when used as a loop, see [OxAgLoopStart]
+/// - amount_scaling - how the sound is scaled by the amount carried by an event, see [OxAgAmountScaling]
+/// - streaming - whether the sound is streamed from its file, see [OxAgStreaming]
+/// - ducks - whether the other categories get ducked while the sound plays, see [OxAgDucking](crate::mixer::OxAgDucking)
+/// - loudness_target - the loudness the sound gets normalized to, see [OxAgLoudnessTarget]
+#[derive(Debug, Clone)]
 pub struct OxAgSoundConfig {
     pub(crate) path: String,
     pub(crate) settings: StaticSoundSettings,
+    pub(crate) loop_start: OxAgLoopStart,
+    pub(crate) amount_scaling: OxAgAmountScaling,
+    pub(crate) streaming: OxAgStreaming,
+    pub(crate) ducks: bool,
+    pub(crate) loudness_target: OxAgLoudnessTarget,
 }
 
 impl OxAgSoundConfig {
     /// # Params
     /// - path - the path to the audio file, supported formats are: **mp3**, **ogg**, **wav**, **flac**
     pub fn new(path: &str) -> OxAgSoundConfig {
-        OxAgSoundConfig {
-            path: path.to_string(),
-            settings: StaticSoundSettings::default(),
-        }
+        OxAgSoundConfig::new_with_settings(path, StaticSoundSettings::default())
     }
 
     /// # Params
     /// - path - the path to the audio file, supported formats are: **mp3**, **ogg**, **wav**, **flac**
     /// - settings - fully customisable sound settings from the kira library
     pub fn new_with_settings(path: &str, settings: StaticSoundSettings) -> OxAgSoundConfig {
-        OxAgSoundConfig { path: path.to_string(), settings }
+        OxAgSoundConfig {
+            path: path.to_string(),
+            settings,
+            loop_start: OxAgLoopStart::default(),
+            amount_scaling: OxAgAmountScaling::default(),
+            streaming: OxAgStreaming::default(),
+            ducks: false,
+            loudness_target: OxAgLoudnessTarget::default(),
+        }
     }
 
     /// # Params
@@ -43,7 +92,7 @@ impl OxAgSoundConfig {
                 end: EndPosition::EndOfAudio,
             });
 
-        OxAgSoundConfig { path: path.to_string(), settings }
+        OxAgSoundConfig::new_with_settings(path, settings)
     }
 
     /// # Params
@@ -53,7 +102,7 @@ impl OxAgSoundConfig {
         let settings = StaticSoundSettings::new()
             .volume(Value::Fixed(Volume::Amplitude(amplitude)));
 
-        OxAgSoundConfig { path: path.to_string(), settings }
+        OxAgSoundConfig::new_with_settings(path, settings)
     }
 
     /// # Params
@@ -70,7 +119,7 @@ impl OxAgSoundConfig {
             })
             .volume(Value::Fixed(Volume::Amplitude(amplitude)));
 
-        OxAgSoundConfig { path: path.to_string(), settings }
+        OxAgSoundConfig::new_with_settings(path, settings)
     }
 
     /// When the sound gets played it will get looped infinitely by default
@@ -86,7 +135,117 @@ impl OxAgSoundConfig {
         self.settings = self.settings.volume(Value::Fixed(Volume::Amplitude(amplitude)));
     }
 
+    /// Sets where the sound starts when it's used as a [TileType](robotics_lib::world::tile::TileType)
+    /// or [WeatherType](robotics_lib::world::environmental_conditions::WeatherType) loop, see [OxAgLoopStart]
+    pub fn loop_start(&mut self, loop_start: OxAgLoopStart) {
+        self.loop_start = loop_start;
+    }
+
+    /// Sets how the sound is scaled by the amount carried by the [Event](robotics_lib::event::events::Event) it's played for,
+    /// see [OxAgAmountScaling]
+    pub fn amount_scaling(&mut self, amount_scaling: OxAgAmountScaling) {
+        self.amount_scaling = amount_scaling;
+    }
+
+    /// Sets whether the sound is streamed from its file instead of being decoded in memory, see [OxAgStreaming]
+    ///
+    /// Streaming applies to the sounds played with [OxAgAudioTool::play_audio](crate::OxAgAudioTool::play_audio),
+    /// the [TileType](robotics_lib::world::tile::TileType), [WeatherType](robotics_lib::world::environmental_conditions::WeatherType)
+    /// and [Content](robotics_lib::world::tile::Content) loops and the music
+    pub fn streaming(&mut self, streaming: OxAgStreaming) {
+        self.streaming = streaming;
+    }
+
+    /// While the sound plays, the tracks of the other [OxAgSoundCategory](crate::mixer::OxAgSoundCategory)s get ducked,
+    /// see [OxAgDucking](crate::mixer::OxAgDucking)
+    ///
+    /// Only one-shots duck: the [Event](robotics_lib::event::events::Event) sounds, the sounds played with
+    /// [OxAgAudioTool::play_audio](crate::OxAgAudioTool::play_audio), the content transitions and the ambient one-shots.
+    /// Loops ignore it, they would duck the other categories for as long as they play
+    pub fn ducks(&mut self) {
+        self.ducks = true;
+    }
+
+    /// Overrides the loudness the sound gets normalized to, see [OxAgLoudnessTarget]
+    /// and [OxAgLoudnessNormalization](crate::loudness::OxAgLoudnessNormalization)
+    pub fn loudness_target(&mut self, loudness_target: OxAgLoudnessTarget) {
+        self.loudness_target = loudness_target;
+    }
+
+    /// Returns the path to the audio file
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Returns the [StaticSoundSettings] of the sound
+    pub fn settings(&self) -> &StaticSoundSettings {
+        &self.settings
+    }
+
+    /// Returns the config played on the given destination, unless it's played on a destination other than the main track already
+    pub(crate) fn routed_to(&self, destination: OutputDestination) -> OxAgSoundConfig {
+        let mut sound_config = self.clone();
+
+        if sound_config.settings.output_destination == OutputDestination::MAIN_TRACK {
+            sound_config.settings.output_destination = destination;
+        }
+
+        sound_config
+    }
+
+    /// Returns whether the sound gets streamed, according to its [OxAgStreaming]
+    pub(crate) fn is_streamed(&self) -> bool {
+        match self.streaming {
+            OxAgStreaming::Never => false,
+            OxAgStreaming::Always => true,
+            OxAgStreaming::AboveSize(size) => fs::metadata(&self.path).is_ok_and(|metadata| metadata.len() > size),
+        }
+    }
+
     pub(crate) fn to_sound_data(&self) -> Result<StaticSoundData, OxAgAudioToolError> {
-        Ok(StaticSoundData::from_file(&self.path, self.settings)?)
+        StaticSoundData::from_file(&self.path, self.settings).map_err(|source| self.file_error(source))
+    }
+
+    /// Returns the sound data streamed from the file instead of being fully decoded in memory
+    pub(crate) fn to_streaming_sound_data(&self) -> Result<StreamingSoundData<FromFileError>, OxAgAudioToolError> {
+        let settings = StreamingSoundSettings::new()
+            .start_time(self.settings.start_time)
+            .playback_region(self.settings.playback_region)
+            .loop_region(self.settings.loop_region)
+            .volume(self.settings.volume)
+            .playback_rate(self.settings.playback_rate)
+            .panning(self.settings.panning)
+            .output_destination(self.settings.output_destination)
+            .fade_in_tween(self.settings.fade_in_tween);
+
+        StreamingSoundData::from_file(&self.path, settings).map_err(|source| self.file_error(source))
+    }
+
+    fn file_error(&self, source: FromFileError) -> OxAgAudioToolError {
+        OxAgAudioToolError::FileError {
+            path: self.path.clone(),
+            source,
+        }
+    }
+
+    /// Returns the sound decoded in memory or, if it gets streamed, checks that the file can be opened
+    pub(crate) fn to_sound_source(&self) -> Result<SoundSource, OxAgAudioToolError> {
+        match self.is_streamed() {
+            true => Ok(SoundSource::Streaming(self.clone(), self.to_streaming_sound_data()?.duration())),
+            false => Ok(SoundSource::Static(self.to_sound_data()?)),
+        }
+    }
+
+    pub(crate) fn to_loaded_sound(&self) -> Result<LoadedSound, OxAgAudioToolError> {
+        Ok(LoadedSound {
+            config: self.clone(),
+            asset: self.path.clone(),
+            source: self.to_sound_source()?,
+            loop_start: self.loop_start,
+            amount_scaling: self.amount_scaling,
+            ducks: self.ducks,
+            volume: self.settings.volume,
+            loudness_target: self.loudness_target,
+        })
     }
 }
\ No newline at end of file
@@ -0,0 +1,18 @@
+use crate::mixer::OxAgSoundCategory;
+use crate::validation::OxAgMappingKey;
+use kira::sound::PlaybackState;
+
+/// A sound the tool is playing, see [OxAgAudioTool::playing_loops](crate::OxAgAudioTool::playing_loops)
+/// and [OxAgAudioTool::playing_one_shots](crate::OxAgAudioTool::playing_one_shots)
+#[derive(Debug, Clone, PartialEq)]
+pub struct OxAgSoundStatus {
+    /// What the sound is mapped to, [None] if it isn't mapped to anything,
+    /// e.g. it was played with [OxAgAudioTool::play_audio](crate::OxAgAudioTool::play_audio)
+    pub key: Option<OxAgMappingKey>,
+    /// The track the sound plays on
+    pub category: OxAgSoundCategory,
+    /// Whether the sound is playing, pausing, paused or stopping
+    pub state: PlaybackState,
+    /// The playback position in seconds
+    pub position: f64,
+}
@@ -0,0 +1,939 @@
+use crate::amount_scaling::{OxAgAmountScaling, OxAgCurve};
+use crate::backend::{render_silently, Device, SilenceReason};
+use crate::builder::OxAgAudioToolBuilder;
+use crate::content_transition::{ContentTransitions, OxAgContentMatch};
+use crate::energy_monitor::{EnergyMonitor, OxAgLowEnergyWarning};
+use crate::limiter::MasterBus;
+use crate::loudness::{integrated_loudness, rms};
+use crate::mixer::{DuckState, DuckerBuilder, OxAgDucking, OxAgSoundCategory};
+use crate::playback::{Debounce, LoadedSound, LoopSwitcher, PlayingLoop, SoundSource};
+use crate::playlist::{OxAgPlaylist, OxAgPlaylistOrder, OxAgRepeatMode, Playlist};
+use crate::sound_config::{OxAgLoopStart, OxAgSoundConfig, OxAgStreaming};
+use crate::timing::{OxAgTimeSource, ToolClock};
+use crate::validation::{OxAgMappingKey, OxAgValidationProblem};
+use crate::error::error::OxAgAudioToolError;
+use crate::OxAgAudioTool;
+use kira::clock::clock_info::{ClockInfo, ClockInfoProvider, MockClockInfoProviderBuilder};
+use kira::clock::{ClockId, ClockSpeed, ClockTime};
+use kira::dsp::Frame;
+use kira::manager::backend::mock::MockBackend;
+use kira::manager::backend::{Backend, Renderer};
+use kira::manager::{AudioManager, AudioManagerSettings};
+use kira::sound::PlaybackState;
+use kira::track::TrackBuilder;
+use kira::modulator::value_provider::MockModulatorValueProviderBuilder;
+use kira::track::effect::{Effect, EffectBuilder};
+use kira::sound::static_sound::{StaticSoundData, StaticSoundSettings};
+use kira::tween::{Tween, Value};
+use kira::sound::PlaybackPosition;
+use kira::{OutputDestination, Volume};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use robotics_lib::event::events::Event;
+use robotics_lib::world::environmental_conditions::WeatherType;
+use robotics_lib::world::tile::{Content, Tile, TileType};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// A backend that hands its renderer over to the test instead of playing it
+struct CapturingBackend(Arc<Mutex<Option<Renderer>>>);
+
+impl Backend for CapturingBackend {
+    type Settings = Arc<Mutex<Option<Renderer>>>;
+    type Error = ();
+
+    fn setup(settings: Self::Settings) -> Result<(Self, u32), Self::Error> {
+        Ok((CapturingBackend(settings), 48000))
+    }
+
+    fn start(&mut self, renderer: Renderer) -> Result<(), Self::Error> {
+        *self.0.lock().unwrap() = Some(renderer);
+        Ok(())
+    }
+}
+
+/// A device that opens but never starts
+struct BrokenDevice;
+
+impl Device for BrokenDevice {
+    fn open() -> Result<(Self, u32), String> {
+        Ok((BrokenDevice, 44100))
+    }
+
+    fn start(&mut self) -> Result<(), String> {
+        Err("the device can't start".to_string())
+    }
+
+    fn play(&mut self, _: Renderer) {
+        panic!("the renderer was handed to a device that didn't start");
+    }
+
+    fn failure(&mut self) -> Option<(Renderer, String)> {
+        None
+    }
+}
+
+/// Returns the clocks of the audio thread when the only clock is at the given tick, together with the clock
+fn clock_at(ticks: u64) -> (ClockInfoProvider<'static>, ClockId) {
+    let mut builder = MockClockInfoProviderBuilder::new(1);
+    let clock = builder
+        .add(ClockInfo {
+            ticking: true,
+            ticks,
+            fractional_position: 0.0,
+        })
+        .unwrap();
+
+    (builder.build(), clock)
+}
+
+/// Runs a second of a full scale signal through the effect, returning the last output amplitude
+fn settle(effect: &mut Box<dyn Effect>, clock_info_provider: &ClockInfoProvider) -> f32 {
+    let modulator_value_provider = MockModulatorValueProviderBuilder::new(0).build();
+    effect.on_start_processing();
+
+    (0..1000)
+        .map(|_| effect.process(Frame::from_mono(1.0), 0.001, clock_info_provider, &modulator_value_provider))
+        .last()
+        .unwrap()
+        .left
+}
+
+/// Returns an empty sound told apart from the others by its sample rate
+fn tagged_sound(tag: u32) -> LoadedSound {
+    let mut sound = silent_loop(&format!("transition_{}.wav", tag));
+    sound.source = SoundSource::Static(StaticSoundData {
+        sample_rate: tag,
+        frames: Arc::new([]),
+        settings: StaticSoundSettings::default(),
+    });
+    sound
+}
+
+/// Returns the tag of a sound returned by [tagged_sound]
+fn tag_of(sound: &LoadedSound) -> Option<u32> {
+    match &sound.source {
+        SoundSource::Static(data) => Some(data.sample_rate),
+        SoundSource::Streaming(..) => None,
+    }
+}
+
+/// Maps the transition to an empty sound tagged with `tag`, see [tagged_sound]
+fn insert_tagged(transitions: &mut ContentTransitions, from: &OxAgContentMatch, to: &OxAgContentMatch, tag: u32) {
+    transitions.insert(from, to, tagged_sound(tag));
+}
+
+/// Returns a clock whose time only flows when it's advanced
+fn manual_clock() -> ToolClock {
+    let mut clock = ToolClock::default();
+    clock.set_source(OxAgTimeSource::Manual);
+    clock
+}
+
+/// Returns a second of silence loaded from the given asset
+fn silent_loop(asset: &str) -> LoadedSound {
+    let config = OxAgSoundConfig::new(asset);
+
+    LoadedSound {
+        asset: asset.to_string(),
+        source: SoundSource::Static(StaticSoundData {
+            sample_rate: 100,
+            frames: Arc::new([Frame::ZERO; 100]),
+            settings: config.settings,
+        }),
+        loop_start: config.loop_start,
+        amount_scaling: config.amount_scaling,
+        ducks: config.ducks,
+        volume: Value::Fixed(Volume::Amplitude(1.0)),
+        loudness_target: config.loudness_target,
+        config,
+    }
+}
+
+/// Writes a second of silence as a wav file in the temporary directory, returning its path
+fn silent_wav(name: &str) -> PathBuf {
+    constant_wav(name, 0)
+}
+
+/// Writes a second of the same sample, at 8 kHz, as a wav file in the temporary directory, returning its path
+fn constant_wav(name: &str, sample: i16) -> PathBuf {
+    let sample_rate: u32 = 8000;
+    let data_size = sample_rate * 2;
+
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(b"RIFF");
+    bytes.extend_from_slice(&(36 + data_size).to_le_bytes());
+    bytes.extend_from_slice(b"WAVEfmt ");
+    bytes.extend_from_slice(&16u32.to_le_bytes());
+    bytes.extend_from_slice(&1u16.to_le_bytes());
+    bytes.extend_from_slice(&1u16.to_le_bytes());
+    bytes.extend_from_slice(&sample_rate.to_le_bytes());
+    bytes.extend_from_slice(&(sample_rate * 2).to_le_bytes());
+    bytes.extend_from_slice(&2u16.to_le_bytes());
+    bytes.extend_from_slice(&16u16.to_le_bytes());
+    bytes.extend_from_slice(b"data");
+    bytes.extend_from_slice(&data_size.to_le_bytes());
+    for _ in 0..sample_rate {
+        bytes.extend_from_slice(&sample.to_le_bytes());
+    }
+
+    let path = std::env::temp_dir().join(format!("oxagaudiotool_{}_{}.wav", std::process::id(), name));
+    std::fs::write(&path, bytes).unwrap();
+    path
+}
+
+/// Returns a tool playing through a new mock audio manager, with loops for the given [TileType]s
+fn tool_with_tiles(settings: AudioManagerSettings<MockBackend>, tile_types: &[(TileType, OxAgSoundConfig)]) -> OxAgAudioTool<MockBackend> {
+    let mut settings = settings;
+    let master_bus = MasterBus::add_to(&mut settings.main_track_builder);
+    let audio_manager = AudioManager::new(settings).unwrap();
+    let output = audio_manager.main_track();
+
+    OxAgAudioTool::from_parts(Arc::new(Mutex::new(audio_manager)), output, master_bus, SilenceReason::default(), &[], tile_types, &[]).unwrap()
+}
+
+/// Returns a tool playing on a track of its own of the shared audio manager, with loops for the given [TileType]s
+fn shared_tool(audio_manager: &Arc<Mutex<AudioManager<MockBackend>>>, tile_types: &[(TileType, OxAgSoundConfig)]) -> OxAgAudioTool<MockBackend> {
+    let mut builder = TrackBuilder::new();
+    let master_bus = MasterBus::add_to(&mut builder);
+    let output = audio_manager.lock().unwrap().add_sub_track(builder).unwrap();
+
+    OxAgAudioTool::from_parts(audio_manager.clone(), output, master_bus, SilenceReason::default(), &[], tile_types, &[]).unwrap()
+}
+
+/// Renders the given amount of frames, returning the last one
+fn render(audio_manager: &Arc<Mutex<AudioManager<MockBackend>>>, frames: usize) -> Frame {
+    let mut audio_manager = audio_manager.lock().unwrap();
+    let backend = audio_manager.backend_mut();
+    backend.on_start_processing();
+
+    (0..frames).map(|_| backend.process()).last().unwrap()
+}
+
+/// Plays a playlist of three silent tracks with the given order and repeat mode
+fn three_tracks(name: &str, order: OxAgPlaylistOrder, repeat: OxAgRepeatMode) -> Playlist {
+    let mut audio_manager = AudioManager::<MockBackend>::new(AudioManagerSettings::default()).unwrap();
+    let timeline = audio_manager.add_clock(ClockSpeed::TicksPerSecond(1000.0)).unwrap();
+
+    let mut config = OxAgPlaylist::new();
+    config.order(order);
+    config.repeat(repeat);
+
+    for index in 0..3 {
+        let track = silent_wav(&format!("{}_{}", name, index));
+        config.add_track(OxAgSoundConfig::new(track.to_str().unwrap()));
+    }
+
+    let playlist = Playlist::play(&mut audio_manager, &timeline, OutputDestination::MAIN_TRACK, &config).unwrap();
+
+    for track in config.tracks.iter() {
+        std::fs::remove_file(&track.path).unwrap();
+    }
+
+    playlist
+}
+
+/// Returns the event of the robot moving onto an empty tile of the given type
+fn moved_on(tile_type: TileType) -> Event {
+    Event::Moved(
+        Tile {
+            tile_type,
+            content: Content::None,
+            elevation: 0,
+        },
+        (0, 0),
+    )
+}
+
+/// Returns `seconds` of a full scale 1 kHz sine, sampled at 48 kHz on both channels as a decoded mono file
+fn sine(seconds: usize) -> Vec<Frame> {
+    (0..48_000 * seconds)
+        .map(|index| Frame::from_mono((2.0 * std::f64::consts::PI * 1000.0 * index as f64 / 48_000.0).sin() as f32))
+        .collect()
+}
+
+/// Returns the tag of the sound of the transition from a tree to a rock at an already known coordinate
+fn tree_to_rock(transitions: &mut ContentTransitions) -> Option<u32> {
+    transitions.remember((0, 0), &Content::Tree(1));
+    transitions.transition((0, 0), &Content::Rock(1)).and_then(|(_, sound)| tag_of(sound))
+}
+
+#[test]
+fn debounce_holds_the_switch_for_min_steps() {
+    let mut debounce = Debounce::default();
+    debounce.min_steps = 3;
+
+    assert!(!debounce.settle(TileType::Sand, Some(&TileType::Grass), Duration::ZERO));
+    assert!(!debounce.settle(TileType::Sand, Some(&TileType::Grass), Duration::ZERO));
+    assert!(debounce.settle(TileType::Sand, Some(&TileType::Grass), Duration::ZERO));
+}
+
+#[test]
+fn debounce_holds_the_switch_for_min_time() {
+    let mut debounce = Debounce::default();
+    debounce.min_time = Duration::from_secs(2);
+
+    assert!(!debounce.settle(TileType::Sand, Some(&TileType::Grass), Duration::ZERO));
+    assert!(!debounce.settle(TileType::Sand, Some(&TileType::Grass), Duration::from_secs(1)));
+    assert!(debounce.settle(TileType::Sand, Some(&TileType::Grass), Duration::from_secs(2)));
+}
+
+#[test]
+fn debounce_resets_when_returning_to_the_current_tile() {
+    let mut debounce = Debounce::default();
+    debounce.min_steps = 2;
+
+    assert!(!debounce.settle(TileType::Sand, Some(&TileType::Grass), Duration::ZERO));
+    assert!(debounce.settle(TileType::Grass, Some(&TileType::Grass), Duration::ZERO));
+    assert!(!debounce.settle(TileType::Sand, Some(&TileType::Grass), Duration::ZERO));
+    assert!(!debounce.is_settled(&TileType::Sand, Some(&TileType::Grass), Duration::ZERO));
+}
+
+#[test]
+fn debounce_restarts_when_the_pending_tile_changes() {
+    let mut debounce = Debounce::default();
+    debounce.min_steps = 2;
+
+    assert!(!debounce.settle(TileType::Sand, Some(&TileType::Grass), Duration::ZERO));
+    assert!(!debounce.settle(TileType::Snow, Some(&TileType::Grass), Duration::ZERO));
+    assert!(debounce.settle(TileType::Snow, Some(&TileType::Grass), Duration::ZERO));
+}
+
+#[test]
+fn debounce_releases_a_held_switch_once_min_time_passes() {
+    let mut debounce = Debounce::default();
+    debounce.min_time = Duration::from_secs(2);
+
+    assert!(!debounce.settle(TileType::Sand, Some(&TileType::Grass), Duration::ZERO));
+    assert!(!debounce.is_settled(&TileType::Sand, Some(&TileType::Grass), Duration::from_secs(1)));
+    assert!(debounce.is_settled(&TileType::Sand, Some(&TileType::Grass), Duration::from_secs(2)));
+    assert!(!debounce.is_settled(&TileType::Snow, Some(&TileType::Grass), Duration::from_secs(2)));
+}
+
+#[test]
+fn content_transition_prefers_the_most_specific_mapping() {
+    let tree = OxAgContentMatch::Content(Content::Tree(0));
+    let rock = OxAgContentMatch::Content(Content::Rock(0));
+    let any = OxAgContentMatch::Any;
+
+    let mut transitions = ContentTransitions::default();
+    insert_tagged(&mut transitions, &any, &any, 4);
+    assert_eq!(tree_to_rock(&mut transitions), Some(4));
+
+    insert_tagged(&mut transitions, &any, &rock, 3);
+    assert_eq!(tree_to_rock(&mut transitions), Some(3));
+
+    insert_tagged(&mut transitions, &tree, &any, 2);
+    assert_eq!(tree_to_rock(&mut transitions), Some(2));
+
+    insert_tagged(&mut transitions, &tree, &rock, 1);
+    assert_eq!(tree_to_rock(&mut transitions), Some(1));
+}
+
+#[test]
+fn content_transition_ignores_unchanged_content() {
+    let mut transitions = ContentTransitions::default();
+    insert_tagged(&mut transitions, &OxAgContentMatch::Any, &OxAgContentMatch::Any, 1);
+    transitions.remember((0, 0), &Content::Tree(1));
+
+    assert!(transitions.transition((0, 0), &Content::Tree(1)).is_none());
+}
+
+#[test]
+fn content_transition_at_an_unknown_coordinate_only_matches_any_old_content() {
+    let tree = OxAgContentMatch::Content(Content::Tree(0));
+    let rock = OxAgContentMatch::Content(Content::Rock(0));
+
+    let mut transitions = ContentTransitions::default();
+    insert_tagged(&mut transitions, &tree, &rock, 1);
+    insert_tagged(&mut transitions, &tree, &OxAgContentMatch::Any, 2);
+    assert!(transitions.transition((0, 0), &Content::Rock(1)).is_none());
+
+    insert_tagged(&mut transitions, &OxAgContentMatch::Any, &rock, 3);
+    assert_eq!(transitions.transition((1, 1), &Content::Rock(1)).and_then(|(_, sound)| tag_of(sound)), Some(3));
+
+    // the coordinate is known from now on
+    transitions.transition((1, 1), &Content::Tree(1));
+    assert_eq!(transitions.transition((1, 1), &Content::Rock(1)).and_then(|(_, sound)| tag_of(sound)), Some(1));
+}
+
+#[test]
+fn content_transition_reports_the_mapping_it_comes_from() {
+    let tree = OxAgContentMatch::Content(Content::Tree(0));
+    let any = OxAgContentMatch::Any;
+
+    let mut transitions = ContentTransitions::default();
+    insert_tagged(&mut transitions, &tree, &any, 1);
+    transitions.remember((0, 0), &Content::Tree(1));
+
+    let key = transitions.transition((0, 0), &Content::Rock(1)).map(|(key, _)| key);
+    let expected = OxAgMappingKey::ContentTransition {
+        from: Some("tree".to_string()),
+        to: None,
+    };
+    assert_eq!(key, Some(expected));
+    assert_eq!(transitions.config(&Some("tree".to_string()), &None).map(|config| config.path()), Some("transition_1.wav"));
+}
+
+#[test]
+fn manual_clock_only_flows_when_advanced() {
+    let mut clock = manual_clock();
+    let start = clock.now();
+
+    std::thread::sleep(Duration::from_millis(5));
+    assert_eq!(clock.now(), start);
+
+    clock.advance(Duration::from_secs(3));
+    assert_eq!(clock.now(), start + Duration::from_secs(3));
+}
+
+#[test]
+fn wall_clock_ignores_manual_advances() {
+    let mut clock = ToolClock::default();
+    clock.advance(Duration::from_secs(3600));
+
+    assert!(clock.now() < Duration::from_secs(3600));
+}
+
+#[test]
+fn clock_carries_on_when_the_source_changes() {
+    let mut clock = manual_clock();
+    clock.advance(Duration::from_secs(10));
+
+    clock.set_source(OxAgTimeSource::WallClock);
+    assert!(clock.now() >= Duration::from_secs(10));
+
+    clock.set_source(OxAgTimeSource::Manual);
+    let now = clock.now();
+    clock.advance(Duration::from_secs(1));
+    assert_eq!(clock.now(), now + Duration::from_secs(1));
+}
+
+#[test]
+fn debounce_follows_the_manual_clock() {
+    let mut clock = manual_clock();
+    let mut debounce = Debounce::default();
+    debounce.min_time = Duration::from_secs(2);
+
+    assert!(!debounce.settle(TileType::Sand, Some(&TileType::Grass), clock.now()));
+
+    for _ in 0..3 {
+        clock.advance(Duration::from_millis(500));
+        assert!(!debounce.is_settled(&TileType::Sand, Some(&TileType::Grass), clock.now()));
+    }
+
+    clock.advance(Duration::from_millis(500));
+    assert!(debounce.is_settled(&TileType::Sand, Some(&TileType::Grass), clock.now()));
+}
+
+#[test]
+fn loop_switcher_holds_a_loop_for_the_min_dwell() {
+    let mut audio_manager = AudioManager::<MockBackend>::new(AudioManagerSettings::default()).unwrap();
+    let mut clock = manual_clock();
+    let mut switcher = LoopSwitcher::default();
+    switcher.min_dwell = Duration::from_secs(2);
+
+    let grass = silent_loop("grass");
+    let sand = silent_loop("sand");
+    let mut current: Option<PlayingLoop<TileType>> = None;
+    let current_key = |current: &Option<PlayingLoop<TileType>>| current.as_ref().map(|playing| playing.key);
+    let mut rng = StdRng::seed_from_u64(0);
+
+    assert!(switcher.switch(&mut audio_manager, &mut current, TileType::Grass, Some(&grass), &mut rng, clock.now()).unwrap());
+    assert_eq!(current_key(&current), Some(TileType::Grass));
+
+    clock.advance(Duration::from_secs(1));
+    assert!(!switcher.switch(&mut audio_manager, &mut current, TileType::Sand, Some(&sand), &mut rng, clock.now()).unwrap());
+    assert_eq!(current_key(&current), Some(TileType::Grass));
+
+    clock.advance(Duration::from_secs(1));
+    assert!(switcher.switch(&mut audio_manager, &mut current, TileType::Sand, Some(&sand), &mut rng, clock.now()).unwrap());
+    assert_eq!(current_key(&current), Some(TileType::Sand));
+}
+
+#[test]
+fn update_drives_the_tile_debounce_in_game_ticks() {
+    let grass = silent_wav("grass");
+    let sand = silent_wav("sand");
+
+    let mut audio = tool_with_tiles(
+        AudioManagerSettings::default(),
+        &[
+            (TileType::Grass, OxAgSoundConfig::new(grass.to_str().unwrap())),
+            (TileType::Sand, OxAgSoundConfig::new(sand.to_str().unwrap())),
+        ],
+    );
+    audio.set_time_source(OxAgTimeSource::Manual);
+    audio.set_tile_debounce(1, Duration::from_secs(2));
+    let tick = Duration::from_millis(500);
+
+    audio.play_audio_based_on_event(&moved_on(TileType::Grass)).unwrap();
+    assert_eq!(audio.current_tile_type(), None);
+
+    for _ in 0..3 {
+        audio.update(tick).unwrap();
+        assert_eq!(audio.current_tile_type(), None);
+    }
+
+    audio.update(tick).unwrap();
+    assert_eq!(audio.current_tile_type(), Some(TileType::Grass));
+
+    audio.play_audio_based_on_event(&moved_on(TileType::Sand)).unwrap();
+    audio.update(Duration::from_secs(1)).unwrap();
+    assert_eq!(audio.current_tile_type(), Some(TileType::Grass));
+
+    audio.update(Duration::from_secs(1)).unwrap();
+    assert_eq!(audio.current_tile_type(), Some(TileType::Sand));
+
+    std::fs::remove_file(grass).unwrap();
+    std::fs::remove_file(sand).unwrap();
+}
+
+#[test]
+fn silent_rendering_carries_on_when_the_device_fails_to_start() {
+    let renderer = Arc::default();
+    let settings = AudioManagerSettings::<CapturingBackend> {
+        backend_settings: Arc::clone(&renderer),
+        ..Default::default()
+    };
+    let mut audio_manager = AudioManager::new(settings).unwrap();
+    let clock = audio_manager.add_clock(ClockSpeed::TicksPerSecond(1000.0)).unwrap();
+    clock.start().unwrap();
+
+    let renderer = renderer.lock().unwrap().take().unwrap();
+    let silence_reason = SilenceReason::default();
+    let stopped = Arc::new(AtomicBool::new(false));
+    let thread = {
+        let silence_reason = silence_reason.clone();
+        let stopped = stopped.clone();
+        thread::spawn(move || render_silently::<BrokenDevice>(renderer, Some(Duration::ZERO), silence_reason, stopped))
+    };
+
+    thread::sleep(Duration::from_millis(100));
+    let ticks = clock.time().ticks;
+    thread::sleep(Duration::from_millis(100));
+
+    assert!(clock.time().ticks > ticks);
+    assert_eq!(silence_reason.lock().unwrap().as_deref(), Some("the device can't start"));
+
+    stopped.store(true, Ordering::SeqCst);
+    thread.join().unwrap();
+}
+
+#[test]
+fn ducking_releases_on_the_audio_thread_once_the_clock_passes_the_end() {
+    let (ducked_clock, clock) = clock_at(50);
+    let (released_clock, _) = clock_at(100);
+
+    let shared = Arc::new(Mutex::new(DuckState {
+        ducking: OxAgDucking::new(0.5),
+        until: Some(ClockTime { clock, ticks: 100 }),
+    }));
+    let (mut ducker, _) = DuckerBuilder { shared }.build();
+
+    assert!((settle(&mut ducker, &ducked_clock) - 0.5).abs() < 1e-6);
+    assert!((settle(&mut ducker, &released_clock) - 1.0).abs() < 1e-6);
+}
+
+#[test]
+fn full_scale_sine_measures_minus_3_db() {
+    let frames = sine(5);
+
+    assert!((rms(&frames).unwrap() + 3.01).abs() < 0.05);
+    assert!((integrated_loudness(&frames, 48_000).unwrap() + 3.01).abs() < 0.05);
+}
+
+#[test]
+fn silence_is_gated_out_of_the_integrated_loudness() {
+    let silence = vec![Frame::ZERO; 48_000 * 10];
+    assert!(integrated_loudness(&silence, 48_000).is_none());
+    assert!(rms(&silence).is_none());
+
+    let mut frames = sine(10);
+    frames.extend(silence);
+    assert!((integrated_loudness(&frames, 48_000).unwrap() + 3.01).abs() < 0.1);
+}
+
+#[test]
+fn validation_reports_every_problem() {
+    let wav = silent_wav("validation");
+    let wav = wav.to_str().unwrap();
+    let garbage = std::env::temp_dir().join(format!("oxagaudiotool_{}_garbage.wav", std::process::id()));
+    std::fs::write(&garbage, b"not a sound").unwrap();
+
+    let report = OxAgAudioToolBuilder::new()
+        .event(Event::Ready, OxAgSoundConfig::new("missing.wav"))
+        .tile_type(TileType::Grass, OxAgSoundConfig::new(garbage.to_str().unwrap()))
+        .tile_type(TileType::Sand, OxAgSoundConfig::new(wav))
+        .tile_type(TileType::Sand, OxAgSoundConfig::new(wav))
+        .weather_type(WeatherType::Sunny, OxAgSoundConfig::new_with_volume(wav, -1.0))
+        .weather_type(WeatherType::Rainy, OxAgSoundConfig::new_with_settings(wav, StaticSoundSettings::new().loop_region(5.0..)))
+        .validate();
+
+    let problems = &report.problems;
+    assert!(!report.is_valid());
+    assert_eq!(problems.len(), 5);
+    assert!(matches!(&problems[0], OxAgValidationProblem::MissingFile { key: OxAgMappingKey::Event(event), .. } if event == "ready"));
+    assert!(matches!(&problems[1], OxAgValidationProblem::UnsupportedFormat { key: OxAgMappingKey::TileType(TileType::Grass), .. }));
+    assert!(matches!(&problems[2], OxAgValidationProblem::DuplicateMapping(OxAgMappingKey::TileType(TileType::Sand))));
+    assert!(matches!(&problems[3], OxAgValidationProblem::InvalidVolume { key: OxAgMappingKey::WeatherType(WeatherType::Sunny), .. }));
+    assert!(matches!(
+        &problems[4],
+        OxAgValidationProblem::LoopRegionPastEnd { key: OxAgMappingKey::WeatherType(WeatherType::Rainy), position, .. } if *position == 5.0
+    ));
+
+    std::fs::remove_file(wav).unwrap();
+    std::fs::remove_file(garbage).unwrap();
+}
+
+#[test]
+fn validation_lists_the_unmapped_keys() {
+    let report = OxAgAudioToolBuilder::new()
+        .event(Event::Ready, OxAgSoundConfig::new("ready.wav"))
+        .tile_type(TileType::Grass, OxAgSoundConfig::new("grass.wav"))
+        .weather_type(WeatherType::Sunny, OxAgSoundConfig::new("sunny.wav"))
+        .weather_type(WeatherType::Rainy, OxAgSoundConfig::new("rainy.wav"))
+        .validate();
+
+    let coverage = &report.coverage;
+    assert_eq!(coverage.unmapped_events.len(), 9);
+    assert!(!coverage.unmapped_events.contains(&"ready".to_string()));
+    assert!(coverage.unmapped_events.contains(&"moved".to_string()));
+    assert_eq!(coverage.unmapped_tile_types.len(), 11);
+    assert!(!coverage.unmapped_tile_types.contains(&TileType::Grass));
+    assert_eq!(
+        coverage.unmapped_weather_types,
+        vec![WeatherType::Foggy, WeatherType::TropicalMonsoon, WeatherType::TrentinoSnow]
+    );
+}
+
+#[test]
+fn curves_are_capped_at_their_max() {
+    let constant = OxAgCurve::Constant(0.5);
+    assert_eq!(constant.evaluate(0), 0.5);
+    assert_eq!(constant.evaluate(1000), 0.5);
+
+    let linear = OxAgCurve::Linear { base: 1.0, slope: 0.5, max: 3.0 };
+    assert_eq!(linear.evaluate(0), 1.0);
+    assert_eq!(linear.evaluate(2), 2.0);
+    assert_eq!(linear.evaluate(10), 3.0);
+
+    let logarithmic = OxAgCurve::Logarithmic { base: 1.0, scale: 2.0, max: 5.0 };
+    assert_eq!(logarithmic.evaluate(0), 1.0);
+    assert!((logarithmic.evaluate(1) - (1.0 + 2.0 * 2.0_f64.ln())).abs() < 1e-9);
+    assert_eq!(logarithmic.evaluate(1000), 5.0);
+}
+
+#[test]
+fn repetitions_follow_the_curve_rounded() {
+    let interval = Duration::from_millis(80);
+    assert_eq!(OxAgAmountScaling::new().repetitions_for(7), 1);
+
+    let mut constant = OxAgAmountScaling::new();
+    constant.repetitions(OxAgCurve::Constant(2.6), interval);
+    assert_eq!(constant.repetitions_for(0), 3);
+
+    let mut linear = OxAgAmountScaling::new();
+    linear.repetitions(OxAgCurve::Linear { base: 0.0, slope: 1.0, max: 10.0 }, interval);
+    assert_eq!(linear.repetitions_for(0), 0);
+    assert_eq!(linear.repetitions_for(3), 3);
+    assert_eq!(linear.repetitions_for(50), 10);
+
+    let mut logarithmic = OxAgAmountScaling::new();
+    logarithmic.repetitions(OxAgCurve::Logarithmic { base: -1.0, scale: 1.0, max: 4.0 }, interval);
+    assert_eq!(logarithmic.repetitions_for(0), 0);
+    assert_eq!(logarithmic.repetitions_for(19), 2);
+    assert_eq!(logarithmic.repetitions_for(1_000_000), 4);
+}
+
+#[test]
+fn mapping_keys_ignore_the_data_carried() {
+    assert_eq!(OxAgMappingKey::from(&Event::EnergyConsumed(5)), OxAgMappingKey::Event("energy_consumed".to_string()));
+    assert_eq!(OxAgMappingKey::from(&Content::Tree(3)), OxAgMappingKey::Content("tree".to_string()));
+}
+
+#[test]
+fn energy_level_saturates_at_both_ends() {
+    let mut monitor = EnergyMonitor::default();
+
+    assert!(!monitor.track(&Event::Ready));
+    assert!(monitor.track(&Event::EnergyRecharged(usize::MAX)));
+    assert_eq!(monitor.level, monitor.max_level);
+    assert_eq!(monitor.depletion(), 0.0);
+
+    monitor.track(&Event::EnergyConsumed(monitor.max_level / 4));
+    assert_eq!(monitor.depletion(), 0.25);
+
+    monitor.track(&Event::EnergyConsumed(usize::MAX));
+    assert_eq!(monitor.level, 0);
+    assert_eq!(monitor.depletion(), 1.0);
+
+    // a level synced above the maximum is clamped
+    monitor.level = monitor.max_level * 2;
+    assert_eq!(monitor.depletion(), 0.0);
+}
+
+#[test]
+fn energy_warning_plays_only_below_the_threshold() {
+    let mut audio_manager = AudioManager::<MockBackend>::new(AudioManagerSettings::default()).unwrap();
+    let mut monitor = EnergyMonitor::default();
+    let warning = OxAgLowEnergyWarning::new(OxAgSoundConfig::new("warning.wav"), 300);
+    monitor.set_warning(&warning, silent_loop("warning.wav"));
+
+    monitor.update_warning(&mut audio_manager).unwrap();
+    assert!(monitor.warning_handle().is_none());
+
+    monitor.track(&Event::EnergyConsumed(monitor.level - 299));
+    monitor.update_warning(&mut audio_manager).unwrap();
+    assert!(monitor.warning_handle().is_some());
+
+    monitor.track(&Event::EnergyRecharged(1));
+    monitor.update_warning(&mut audio_manager).unwrap();
+    assert!(monitor.warning_handle().is_none());
+}
+
+#[test]
+fn playlist_positions_follow_the_repeat_mode() {
+    let off = three_tracks("playlist_off", OxAgPlaylistOrder::Ordered, OxAgRepeatMode::Off);
+    assert_eq!(off.next_position(0, false), Some((1, None)));
+    assert_eq!(off.next_position(2, false), None);
+
+    let one = three_tracks("playlist_one", OxAgPlaylistOrder::Ordered, OxAgRepeatMode::One);
+    assert_eq!(one.next_position(1, false), Some((1, None)));
+    assert_eq!(one.next_position(1, true), Some((2, None)));
+
+    let all = three_tracks("playlist_all", OxAgPlaylistOrder::Ordered, OxAgRepeatMode::All);
+    assert_eq!(all.next_position(2, false), Some((0, None)));
+}
+
+#[test]
+fn shuffled_playlist_reshuffles_when_it_starts_over() {
+    let playlist = three_tracks("playlist_shuffled", OxAgPlaylistOrder::Shuffled, OxAgRepeatMode::All);
+    assert_eq!(playlist.next_position(0, false), Some((1, None)));
+
+    let (position, order) = playlist.next_position(2, false).unwrap();
+    let mut order = order.unwrap();
+    order.sort();
+    assert_eq!(position, 0);
+    assert_eq!(order, vec![0, 1, 2]);
+}
+
+#[test]
+fn resumed_loop_restarts_where_it_stopped() {
+    let mut settings = AudioManagerSettings::<MockBackend>::default();
+    settings.backend_settings.sample_rate = 100;
+    let mut audio_manager = AudioManager::new(settings).unwrap();
+    let mut switcher = LoopSwitcher::default();
+    let mut rng = StdRng::seed_from_u64(0);
+
+    let mut grass = silent_loop("grass");
+    grass.loop_start = OxAgLoopStart::Resume;
+    let from_the_beginning = StaticSoundSettings::default().playback_region.start;
+    assert_eq!(switcher.start_settings(&grass, StaticSoundSettings::default(), &mut rng).playback_region.start, from_the_beginning);
+
+    let playing = switcher.start(&mut audio_manager, TileType::Grass, &grass, &mut rng, Duration::ZERO).unwrap();
+    audio_manager.backend_mut().on_start_processing();
+    for _ in 0..50 {
+        audio_manager.backend_mut().process();
+    }
+    audio_manager.backend_mut().on_start_processing();
+
+    let position = playing.handle.position();
+    assert!(position > 0.0);
+    switcher.stop(playing);
+
+    let settings = switcher.start_settings(&grass, StaticSoundSettings::default(), &mut rng);
+    assert_eq!(settings.playback_region.start, PlaybackPosition::Seconds(position));
+}
+
+#[test]
+fn random_loop_start_follows_the_seed() {
+    let switcher = LoopSwitcher::default();
+    let mut grass = silent_loop("grass");
+    grass.loop_start = OxAgLoopStart::Random;
+
+    let start = |seed| switcher.start_settings(&grass, StaticSoundSettings::default(), &mut StdRng::seed_from_u64(seed)).playback_region.start;
+    assert_eq!(start(7), start(7));
+    assert_ne!(start(7), StaticSoundSettings::default().playback_region.start);
+}
+
+#[test]
+fn keys_sharing_an_asset_keep_a_single_loop() {
+    let shared = constant_wav("shared", i16::MAX / 4);
+
+    let mut settings = AudioManagerSettings::<MockBackend>::default();
+    settings.backend_settings.sample_rate = 8000;
+
+    let mut audio = tool_with_tiles(
+        settings,
+        &[
+            (TileType::Grass, OxAgSoundConfig::new(shared.to_str().unwrap())),
+            (TileType::Sand, OxAgSoundConfig::new_with_volume(shared.to_str().unwrap(), 0.5)),
+        ],
+    );
+    audio.set_shared_asset_tween(Some(Tween::default()));
+    let audio_manager = audio.audio_manager();
+
+    audio.play_audio_based_on_event(&moved_on(TileType::Grass)).unwrap();
+    let before = render(&audio_manager, 200).left;
+
+    audio.play_audio_based_on_event(&moved_on(TileType::Sand)).unwrap();
+    let after = render(&audio_manager, 200).left;
+
+    assert!(before > 0.0);
+    assert!((after / before - 0.5).abs() < 0.01);
+    assert_eq!(audio_manager.lock().unwrap().num_sounds(), 1);
+
+    let loops = audio.playing_loops();
+    assert_eq!(loops.len(), 1);
+    assert_eq!(loops[0].key, Some(OxAgMappingKey::TileType(TileType::Sand)));
+    assert!(loops[0].position > 0.02);
+
+    std::fs::remove_file(shared).unwrap();
+}
+
+#[test]
+fn stopping_the_tile_category_restarts_the_debounce() {
+    let sand = silent_wav("debounce_sand");
+    let mut audio = tool_with_tiles(AudioManagerSettings::default(), &[(TileType::Sand, OxAgSoundConfig::new(sand.to_str().unwrap()))]);
+    audio.set_tile_debounce(2, Duration::ZERO);
+
+    audio.play_audio_based_on_event(&moved_on(TileType::Sand)).unwrap();
+    audio.stop_category(OxAgSoundCategory::Tile, Tween::default());
+
+    audio.play_audio_based_on_event(&moved_on(TileType::Sand)).unwrap();
+    assert_eq!(audio.current_tile_type(), None);
+
+    audio.play_audio_based_on_event(&moved_on(TileType::Sand)).unwrap();
+    assert_eq!(audio.current_tile_type(), Some(TileType::Sand));
+
+    std::fs::remove_file(sand).unwrap();
+}
+
+#[test]
+fn content_transitions_forget_the_tiles_far_from_the_robot() {
+    let tree = OxAgContentMatch::Content(Content::Tree(0));
+    let rock = OxAgContentMatch::Content(Content::Rock(0));
+
+    let mut transitions = ContentTransitions::default();
+    insert_tagged(&mut transitions, &tree, &rock, 1);
+    transitions.remember((0, 0), &Content::Tree(1));
+    transitions.remember((10, 32), &Content::None);
+    assert_eq!(transitions.transition((0, 0), &Content::Rock(1)).and_then(|(_, sound)| tag_of(sound)), Some(1));
+
+    transitions.remember((0, 0), &Content::Tree(1));
+    transitions.remember((10, 33), &Content::None);
+    assert!(transitions.transition((0, 0), &Content::Rock(1)).is_none());
+}
+
+#[test]
+fn commands_apply_in_order_and_keep_their_errors() {
+    let mut audio = tool_with_tiles(AudioManagerSettings::default(), &[]);
+    let controller = audio.controller();
+
+    thread::spawn(move || {
+        controller.set_master_volume(0.3, Tween::default()).unwrap();
+        controller.set_master_volume(0.6, Tween::default()).unwrap();
+        controller.play_audio(OxAgSoundConfig::new("missing.wav")).unwrap();
+        controller.pause(Tween::default()).unwrap();
+    })
+    .join()
+    .unwrap();
+
+    assert_eq!(audio.master_volume(), 1.0);
+    audio.process_commands();
+
+    assert_eq!(audio.master_volume(), 0.6);
+    assert!(audio.is_paused());
+    assert!(matches!(audio.pop_command_error(), Some(OxAgAudioToolError::FileError { path, .. }) if path == "missing.wav"));
+    assert!(audio.pop_command_error().is_none());
+}
+
+#[test]
+fn context_errors_chain_down_to_the_file_error() {
+    let error = OxAgSoundConfig::new("missing.wav")
+        .to_sound_data()
+        .unwrap_err()
+        .context("loading the sound", Some(OxAgMappingKey::TileType(TileType::Grass)))
+        .context("building the tool", None);
+
+    assert_eq!(error.to_string(), "building the tool failed");
+    assert!(matches!(error.root(), OxAgAudioToolError::FileError { path, .. } if path == "missing.wav"));
+
+    let loading = std::error::Error::source(&error).unwrap();
+    assert_eq!(loading.to_string(), "loading the sound for the tile type Grass failed");
+
+    let file = loading.source().unwrap();
+    assert_eq!(file.to_string(), "could not load the audio file missing.wav");
+    assert!(file.source().is_some());
+}
+
+#[test]
+fn sounds_above_the_size_get_streamed() {
+    let wav = silent_wav("streaming");
+    let mut sound_config = OxAgSoundConfig::new(wav.to_str().unwrap());
+    let size = std::fs::metadata(&wav).unwrap().len();
+
+    sound_config.streaming(OxAgStreaming::AboveSize(size - 1));
+    assert!(sound_config.is_streamed());
+    assert!(matches!(sound_config.to_sound_source().unwrap(), SoundSource::Streaming(..)));
+
+    sound_config.streaming(OxAgStreaming::AboveSize(size));
+    assert!(!sound_config.is_streamed());
+    assert!(matches!(sound_config.to_sound_source().unwrap(), SoundSource::Static(_)));
+
+    std::fs::remove_file(wav).unwrap();
+}
+
+#[test]
+fn clip_counter_counts_the_frames_beyond_full_scale() {
+    let loud = constant_wav("clipping", i16::MAX / 2);
+    let mut settings = AudioManagerSettings::<MockBackend>::default();
+    settings.backend_settings.sample_rate = 8000;
+    let mut audio = tool_with_tiles(settings, &[(TileType::Grass, OxAgSoundConfig::new(loud.to_str().unwrap()))]);
+    let audio_manager = audio.audio_manager();
+
+    audio.play_audio_based_on_event(&moved_on(TileType::Grass)).unwrap();
+    render(&audio_manager, 200);
+    assert_eq!(audio.clipped_frames(), 0);
+
+    audio.set_master_volume(4.0, Tween::default()).unwrap();
+    render(&audio_manager, 200);
+    assert!(audio.clipped_frames() > 100);
+
+    audio.reset_clipped_frames();
+    assert_eq!(audio.clipped_frames(), 0);
+
+    std::fs::remove_file(loud).unwrap();
+}
+
+#[test]
+fn tools_sharing_an_audio_manager_stop_independently() {
+    let loop_wav = constant_wav("shared_manager", i16::MAX / 4);
+    let grass = [(TileType::Grass, OxAgSoundConfig::new(loop_wav.to_str().unwrap()))];
+
+    let mut settings = AudioManagerSettings::<MockBackend>::default();
+    settings.backend_settings.sample_rate = 8000;
+    let audio_manager = Arc::new(Mutex::new(AudioManager::new(settings).unwrap()));
+    let mut first = shared_tool(&audio_manager, &grass);
+    let mut second = shared_tool(&audio_manager, &grass);
+
+    first.play_audio_based_on_event(&moved_on(TileType::Grass)).unwrap();
+    second.play_audio_based_on_event(&moved_on(TileType::Grass)).unwrap();
+    let both = render(&audio_manager, 200).left;
+
+    first.stop_all(Tween::default());
+    render(&audio_manager, 200);
+    let one = render(&audio_manager, 200).left;
+
+    assert!(first.playing_loops().is_empty());
+    let loops = second.playing_loops();
+    assert_eq!(loops.len(), 1);
+    assert_eq!(loops[0].state, PlaybackState::Playing);
+    assert!((one / both - 0.5).abs() < 0.01);
+
+    std::fs::remove_file(loop_wav).unwrap();
+}
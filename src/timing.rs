@@ -0,0 +1,55 @@
+use std::time::{Duration, Instant};
+
+/// Where the time of the debounce, the loop dwell, the adaptive music activity and the other timers of the tool comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OxAgTimeSource {
+    /// The time flows as the real time does
+    #[default]
+    WallClock,
+    /// The time only flows when [OxAgAudioTool::update](crate::OxAgAudioTool::update) is called,
+    /// e.g. from `process_tick` with the duration of a game tick, so that the timers are measured in game ticks
+    /// and behave the same on every run
+    Manual,
+}
+
+/// The time of the tool, measured from when it was created
+pub(crate) struct ToolClock {
+    source: OxAgTimeSource,
+    /// The time when the source was last changed, plus the time advanced manually since then
+    offset: Duration,
+    /// When the source was last changed
+    since: Instant,
+}
+
+impl Default for ToolClock {
+    fn default() -> Self {
+        ToolClock {
+            source: OxAgTimeSource::default(),
+            offset: Duration::ZERO,
+            since: Instant::now(),
+        }
+    }
+}
+
+impl ToolClock {
+    pub(crate) fn now(&self) -> Duration {
+        match self.source {
+            OxAgTimeSource::WallClock => self.offset + self.since.elapsed(),
+            OxAgTimeSource::Manual => self.offset,
+        }
+    }
+
+    /// Advances the time, unless it follows the wall clock
+    pub(crate) fn advance(&mut self, elapsed: Duration) {
+        if self.source == OxAgTimeSource::Manual {
+            self.offset += elapsed;
+        }
+    }
+
+    /// Changes the source, the time carrying on from where it is
+    pub(crate) fn set_source(&mut self, source: OxAgTimeSource) {
+        self.offset = self.now();
+        self.since = Instant::now();
+        self.source = source;
+    }
+}
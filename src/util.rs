@@ -1,4 +1,22 @@
+use crate::error::error::OxAgAudioToolError;
+use kira::CommandError;
 use robotics_lib::event::events::Event;
+use robotics_lib::world::tile::Content;
+use std::sync::{Mutex, MutexGuard};
+
+/// The keys of all the kinds of [Event], see [event_key]
+pub(crate) const EVENT_KEYS: [&str; 10] = [
+    "ready",
+    "terminated",
+    "time_changed",
+    "day_changed",
+    "energy_recharged",
+    "energy_consumed",
+    "moved",
+    "tile_content_updated",
+    "added_to_backpack",
+    "removed_from_backpack",
+];
 
 /// Converts an event to a recognizable key ignoring the event properties
 pub(crate) fn event_key(event: &Event) -> &str {
@@ -14,4 +32,40 @@ pub(crate) fn event_key(event: &Event) -> &str {
         Event::AddedToBackpack(_, _) => { "added_to_backpack" }
         Event::RemovedFromBackpack(_, _) => { "removed_from_backpack" }
     }
-}
\ No newline at end of file
+}
+
+/// Returns the amount carried by an event, if any
+pub(crate) fn event_amount(event: &Event) -> Option<usize> {
+    match event {
+        Event::EnergyRecharged(amount) | Event::EnergyConsumed(amount) => Some(*amount),
+        Event::AddedToBackpack(_, amount) | Event::RemovedFromBackpack(_, amount) => Some(*amount),
+        _ => None,
+    }
+}
+
+/// Converts a content to a recognizable key ignoring the content properties
+pub(crate) fn content_key(content: &Content) -> &str {
+    match content {
+        Content::Rock(_) => { "rock" }
+        Content::Tree(_) => { "tree" }
+        Content::Garbage(_) => { "garbage" }
+        Content::Fire => { "fire" }
+        Content::Coin(_) => { "coin" }
+        Content::Bin(_) => { "bin" }
+        Content::Crate(_) => { "crate" }
+        Content::Bank(_) => { "bank" }
+        Content::Water(_) => { "water" }
+        Content::Market(_) => { "market" }
+        Content::Fish(_) => { "fish" }
+        Content::Building => { "building" }
+        Content::Bush(_) => { "bush" }
+        Content::JollyBlock(_) => { "jolly_block" }
+        Content::Scarecrow => { "scarecrow" }
+        Content::None => { "none" }
+    }
+}
+
+/// Locks the mutex, a thread that panicked while holding it being reported as a [CommandError::MutexPoisoned]
+pub(crate) fn lock<T>(mutex: &Mutex<T>) -> Result<MutexGuard<'_, T>, OxAgAudioToolError> {
+    mutex.lock().map_err(|_| CommandError::MutexPoisoned.into())
+}
@@ -0,0 +1,243 @@
+use crate::sound_config::OxAgSoundConfig;
+use crate::util::{content_key, event_key, EVENT_KEYS};
+use kira::sound::streaming::{StreamingSoundData, StreamingSoundSettings};
+use kira::sound::{EndPosition, FromFileError, PlaybackPosition};
+use kira::tween::Value;
+use robotics_lib::event::events::Event;
+use robotics_lib::world::environmental_conditions::WeatherType;
+use robotics_lib::world::tile::{Content, TileType};
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+use std::time::Duration;
+
+/// All the [TileType]s a sound can be mapped to
+const TILE_TYPES: [TileType; 12] = [
+    TileType::DeepWater,
+    TileType::ShallowWater,
+    TileType::Sand,
+    TileType::Grass,
+    TileType::Street,
+    TileType::Hill,
+    TileType::Mountain,
+    TileType::Snow,
+    TileType::Lava,
+    TileType::Teleport(false),
+    TileType::Teleport(true),
+    TileType::Wall,
+];
+
+/// All the [WeatherType]s a sound can be mapped to
+const WEATHER_TYPES: [WeatherType; 5] = [
+    WeatherType::Sunny,
+    WeatherType::Rainy,
+    WeatherType::Foggy,
+    WeatherType::TropicalMonsoon,
+    WeatherType::TrentinoSnow,
+];
+
+/// What a sound is mapped to
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum OxAgMappingKey {
+    /// The kind of [Event](robotics_lib::event::events::Event), e.g. `"moved"`, the data carried by the event is ignored
+    Event(String),
+    /// The [TileType] of the loop played while the robot is on it
+    TileType(TileType),
+    /// The [WeatherType] of the loop played while the weather is the same
+    WeatherType(WeatherType),
+    /// The kind of [Content](robotics_lib::world::tile::Content), e.g. `"tree"`, the data carried by the content is ignored
+    Content(String),
+    /// The sound given to [OxAgAudioToolBuilder::background_music](crate::builder::OxAgAudioToolBuilder::background_music)
+    BackgroundMusic,
+    /// The transition between two kinds of [Content](robotics_lib::world::tile::Content), [None] matching any content,
+    /// see [OxAgAudioTool::set_content_transition_sound](crate::OxAgAudioTool::set_content_transition_sound)
+    ContentTransition { from: Option<String>, to: Option<String> },
+    /// The warning given to [OxAgAudioTool::set_low_energy_warning](crate::OxAgAudioTool::set_low_energy_warning)
+    LowEnergyWarning,
+}
+
+impl From<&Event> for OxAgMappingKey {
+    /// The key of the kind of the [Event], see [OxAgMappingKey::Event]
+    fn from(event: &Event) -> Self {
+        OxAgMappingKey::Event(event_key(event).to_string())
+    }
+}
+
+impl From<&Content> for OxAgMappingKey {
+    /// The key of the kind of the [Content], see [OxAgMappingKey::Content]
+    fn from(content: &Content) -> Self {
+        OxAgMappingKey::Content(content_key(content).to_string())
+    }
+}
+
+impl Display for OxAgMappingKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OxAgMappingKey::Event(event) => write!(f, "event {}", event),
+            OxAgMappingKey::TileType(tile_type) => write!(f, "tile type {:?}", tile_type),
+            OxAgMappingKey::WeatherType(weather_type) => write!(f, "weather type {:?}", weather_type),
+            OxAgMappingKey::Content(content) => write!(f, "content {}", content),
+            OxAgMappingKey::BackgroundMusic => write!(f, "background music"),
+            OxAgMappingKey::ContentTransition { from, to } => write!(
+                f,
+                "content transition from {} to {}",
+                from.as_deref().unwrap_or("any content"),
+                to.as_deref().unwrap_or("any content")
+            ),
+            OxAgMappingKey::LowEnergyWarning => write!(f, "low energy warning"),
+        }
+    }
+}
+
+/// A problem of the configuration, found before loading any sound
+#[derive(Debug)]
+pub enum OxAgValidationProblem {
+    /// The audio file of the mapping doesn't exist
+    MissingFile { key: OxAgMappingKey, path: String },
+    /// The audio file of the mapping can't be decoded, see the wrapped [FromFileError]
+    UnsupportedFormat { key: OxAgMappingKey, path: String, error: FromFileError },
+    /// The volume of the mapping, as an amplitude, is negative or not finite
+    InvalidVolume { key: OxAgMappingKey, amplitude: f64 },
+    /// The playback rate of the mapping is not positive or not finite
+    InvalidPlaybackRate { key: OxAgMappingKey, factor: f64 },
+    /// The loop region of the mapping starts or ends after the end of its audio file
+    LoopRegionPastEnd { key: OxAgMappingKey, position: f64, duration: Duration },
+    /// The same key is mapped more than once
+    DuplicateMapping(OxAgMappingKey),
+    /// The master volume, as an amplitude, is negative or not finite
+    InvalidMasterVolume(f64),
+}
+
+impl Display for OxAgValidationProblem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OxAgValidationProblem::MissingFile { key, path } => write!(f, "the file {} of the {} doesn't exist", path, key),
+            OxAgValidationProblem::UnsupportedFormat { key, path, error } => {
+                write!(f, "the file {} of the {} can't be decoded: {}", path, key, error)
+            }
+            OxAgValidationProblem::InvalidVolume { key, amplitude } => write!(f, "the volume {} of the {} is not valid", amplitude, key),
+            OxAgValidationProblem::InvalidPlaybackRate { key, factor } => {
+                write!(f, "the playback rate {} of the {} is not valid", factor, key)
+            }
+            OxAgValidationProblem::LoopRegionPastEnd { key, position, duration } => write!(
+                f,
+                "the loop region of the {} reaches {}s, past the end of the file at {}s",
+                key,
+                position,
+                duration.as_secs_f64()
+            ),
+            OxAgValidationProblem::DuplicateMapping(key) => write!(f, "the {} is mapped twice", key),
+            OxAgValidationProblem::InvalidMasterVolume(amplitude) => write!(f, "the master volume {} is not valid", amplitude),
+        }
+    }
+}
+
+/// The keys no sound is mapped to
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OxAgCoverageReport {
+    /// The kinds of [Event](robotics_lib::event::events::Event) without a sound, see [OxAgMappingKey::Event]
+    pub unmapped_events: Vec<String>,
+    /// The [TileType]s without a loop
+    pub unmapped_tile_types: Vec<TileType>,
+    /// The [WeatherType]s without a loop
+    pub unmapped_weather_types: Vec<WeatherType>,
+}
+
+/// Every problem of a configuration together with the keys it leaves without a sound,
+/// see [OxAgAudioToolBuilder::validate](crate::builder::OxAgAudioToolBuilder::validate)
+#[derive(Debug, Default)]
+pub struct OxAgValidationReport {
+    pub problems: Vec<OxAgValidationProblem>,
+    pub coverage: OxAgCoverageReport,
+}
+
+impl OxAgValidationReport {
+    /// Returns whether the configuration has no problems, the coverage doesn't matter
+    pub fn is_valid(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    /// Checks the given mapping, adding its problems to the report
+    pub(crate) fn check_sound_config(&mut self, key: OxAgMappingKey, sound_config: &OxAgSoundConfig) {
+        if let Value::Fixed(volume) = sound_config.settings.volume {
+            let amplitude = volume.as_amplitude();
+
+            if !amplitude.is_finite() || amplitude < 0.0 {
+                self.problems.push(OxAgValidationProblem::InvalidVolume { key: key.clone(), amplitude });
+            }
+        }
+
+        if let Value::Fixed(playback_rate) = sound_config.settings.playback_rate {
+            let factor = playback_rate.as_factor();
+
+            if !factor.is_finite() || factor <= 0.0 {
+                self.problems.push(OxAgValidationProblem::InvalidPlaybackRate { key: key.clone(), factor });
+            }
+        }
+
+        let path = sound_config.path.clone();
+
+        if !Path::new(&path).is_file() {
+            self.problems.push(OxAgValidationProblem::MissingFile { key, path });
+            return;
+        }
+
+        // probing the file as a stream reads its header without decoding it
+        let duration = match StreamingSoundData::from_file(&path, StreamingSoundSettings::default()) {
+            Ok(data) => data.duration(),
+            Err(error) => {
+                self.problems.push(OxAgValidationProblem::UnsupportedFormat { key, path, error });
+                return;
+            }
+        };
+
+        if let Some(region) = sound_config.settings.loop_region {
+            let end = match region.end {
+                EndPosition::EndOfAudio => None,
+                EndPosition::Custom(position) => Some(position),
+            };
+
+            // positions in samples can't be checked without decoding the file
+            let past_end = [Some(region.start), end]
+                .into_iter()
+                .flatten()
+                .filter_map(|position| match position {
+                    PlaybackPosition::Seconds(seconds) => Some(seconds),
+                    PlaybackPosition::Samples(_) => None,
+                })
+                .find(|seconds| *seconds > duration.as_secs_f64());
+
+            if let Some(position) = past_end {
+                self.problems.push(OxAgValidationProblem::LoopRegionPastEnd { key, position, duration });
+            }
+        }
+    }
+
+    /// Fills the coverage with the keys that are not in the given mappings
+    pub(crate) fn check_coverage<'a>(&mut self, mapped: impl Iterator<Item = &'a OxAgMappingKey>) {
+        let mapped: Vec<&OxAgMappingKey> = mapped.collect();
+
+        self.coverage = OxAgCoverageReport {
+            unmapped_events: EVENT_KEYS
+                .iter()
+                .map(|event| event.to_string())
+                .filter(|event| !mapped.contains(&&OxAgMappingKey::Event(event.clone())))
+                .collect(),
+            unmapped_tile_types: TILE_TYPES
+                .into_iter()
+                .filter(|tile_type| !mapped.contains(&&OxAgMappingKey::TileType(*tile_type)))
+                .collect(),
+            unmapped_weather_types: WEATHER_TYPES
+                .into_iter()
+                .filter(|weather_type| !mapped.contains(&&OxAgMappingKey::WeatherType(weather_type.clone())))
+                .collect(),
+        };
+    }
+}
+
+impl Display for OxAgValidationReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let problems: Vec<String> = self.problems.iter().map(|problem| problem.to_string()).collect();
+
+        write!(f, "{}", problems.join("; "))
+    }
+}